@@ -1,17 +1,29 @@
+mod collections;
+mod derived;
+mod transaction;
+
 use crate::maybe::{
     MaybeMutex, MaybeRwLock, MaybeRwLockReadGuard, MaybeRwLockWriteGuard, MaybeSend,
-    MaybeSendStatic, MaybeSendSync, Shared,
+    MaybeSendStatic, MaybeSendSync, MaybeWeak, Shared,
 };
-use crate::{__private, Command};
+use crate::{__private, Command, Subscription};
 use alloc::collections::VecDeque;
 use alloc::vec::Vec;
 use core::fmt::Debug;
 use core::marker::PhantomData;
-use core::sync::atomic::{AtomicBool, Ordering};
-use futures::StreamExt;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use core::task::{Context, Poll};
 use futures::channel::mpsc;
+use futures::{Stream, StreamExt};
 use thiserror::Error;
 
+pub use collections::{
+    DiffSubscriber, MapDiff, SignalMap, SignalMapWriter, SignalVec, SignalVecWriter, VecDiff,
+};
+pub use derived::{JoinSignals, Tracker};
+pub use transaction::{SignalBorrowed, SignalTransaction, batch, read_consistent};
+
 // must be `'static` for interceptors, `MaybeSendSync` for commands
 pub trait Application: MaybeSendSync + 'static {
     type RootModel: Model<ForApp = Self>;
@@ -36,6 +48,13 @@ pub trait Model: MaybeSendSync + 'static {
 
     fn update(&mut self, message: Self::Message) -> Command<Self::Message, Self::ForApp>;
 
+    /// Returns the event sources this model wants to be kept alive.
+    ///
+    /// This is re-evaluated by the host after every update.
+    fn subscriptions(&self) -> Subscription<Self::Message, Self::ForApp> {
+        Subscription::none()
+    }
+
     #[doc(hidden)]
     fn __accumulate_signals(
         &self,
@@ -53,8 +72,14 @@ pub enum Error {
     #[error("the channel to the host is closed")]
     HostChannelClosed,
 
+    #[error("the channel to the host is full")]
+    HostChannelFull,
+
     #[error("the channel to the model getter is closed")]
     ModelGetterChannelClosed,
+
+    #[error("the update was dropped before it completed")]
+    UpdateDropped,
 }
 
 impl From<HostChannelClosed> for Error {
@@ -63,6 +88,15 @@ impl From<HostChannelClosed> for Error {
     }
 }
 
+impl From<TrySendError> for Error {
+    fn from(error: TrySendError) -> Self {
+        match error {
+            TrySendError::Full => Self::HostChannelFull,
+            TrySendError::Closed => Self::HostChannelClosed,
+        }
+    }
+}
+
 impl From<ModelGetterChannelClosedError> for Error {
     fn from(_: ModelGetterChannelClosedError) -> Self {
         Self::ModelGetterChannelClosed
@@ -74,6 +108,16 @@ impl From<ModelGetterChannelClosedError> for Error {
 #[non_exhaustive]
 pub struct HostChannelClosed;
 
+/// The reason a message could not be sent right away through [`crate::Updater::try_send_now`].
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrySendError {
+    #[error("the channel to the host is full")]
+    Full,
+
+    #[error("the channel to the host is closed")]
+    Closed,
+}
+
 #[derive(Error, Debug)]
 #[non_exhaustive]
 #[error("the channel to the model getter is closed")]
@@ -200,6 +244,191 @@ where
     }
 }
 
+/// Observers are ran _after_ the message gets processed by the host and the signals are flushed.
+///
+/// They receive every message that reached the root model along with the [`Command`] it
+/// returned, which has not been started yet.
+///
+/// The model is only observed in its new state, once every message a [`Middleware`] fanned out
+/// has been handled, so each of those messages is paired with the final state rather than its own.
+/// The state before the update can be captured by an [`Interceptor`], which runs first.
+pub trait Observer<A: Application>: MaybeSendSync + 'static {
+    fn observe(
+        &mut self,
+        model: ModelBaseReader<A::RootModel>,
+        message: &<A::RootModel as Model>::Message,
+        command: &Command<<A::RootModel as Model>::Message, A>,
+    );
+}
+
+impl<A, F> Observer<A> for F
+where
+    A: Application,
+    F: FnMut(
+            ModelBaseReader<A::RootModel>,
+            &<A::RootModel as Model>::Message,
+            &Command<<A::RootModel as Model>::Message, A>,
+        ) + MaybeSendSync
+        + 'static,
+{
+    fn observe(
+        &mut self,
+        model: ModelBaseReader<A::RootModel>,
+        message: &<A::RootModel as Model>::Message,
+        command: &Command<<A::RootModel as Model>::Message, A>,
+    ) {
+        self(model, message, command)
+    }
+}
+
+/// Middleware runs _before_ the message gets processed by the host, after the interceptors.
+///
+/// Unlike an [`Interceptor`], a middleware owns the message. It passes it on to the rest of the
+/// chain through [`Next::run`], and is free to rewrite it, swallow it by never calling
+/// [`Next::run`], or fan it out by calling it several times. The returned [`Command`] is run by the
+/// host alongside the commands returned by the updates; the messages it produces go through the
+/// whole chain again.
+pub trait Middleware<A: Application>: MaybeSendSync + 'static {
+    fn process(
+        &mut self,
+        model: ModelBaseReader<A::RootModel>,
+        message: <A::RootModel as Model>::Message,
+        next: Next<'_, A>,
+    ) -> Command<<A::RootModel as Model>::Message, A>;
+}
+
+impl<A, F> Middleware<A> for F
+where
+    A: Application,
+    F: for<'a> FnMut(
+            ModelBaseReader<A::RootModel>,
+            <A::RootModel as Model>::Message,
+            Next<'a, A>,
+        ) -> Command<<A::RootModel as Model>::Message, A>
+        + MaybeSendSync
+        + 'static,
+{
+    fn process(
+        &mut self,
+        model: ModelBaseReader<A::RootModel>,
+        message: <A::RootModel as Model>::Message,
+        next: Next<'_, A>,
+    ) -> Command<<A::RootModel as Model>::Message, A> {
+        self(model, message, next)
+    }
+}
+
+type UpdateFn<'a, A> = dyn FnMut(<<A as Application>::RootModel as Model>::Message) + 'a;
+
+/// The rest of a [`Middleware`] chain, ending with the update of the root model.
+pub struct Next<'a, A: Application> {
+    rest: &'a mut [alloc::boxed::Box<dyn Middleware<A>>],
+    model: &'a ModelBase<A::RootModel>,
+    commands: &'a mut Vec<Command<<A::RootModel as Model>::Message, A>>,
+    update: &'a mut UpdateFn<'a, A>,
+}
+
+impl<'a, A: Application> Next<'a, A> {
+    pub(crate) fn new(
+        chain: &'a mut [alloc::boxed::Box<dyn Middleware<A>>],
+        model: &'a ModelBase<A::RootModel>,
+        commands: &'a mut Vec<Command<<A::RootModel as Model>::Message, A>>,
+        update: &'a mut UpdateFn<'a, A>,
+    ) -> Self {
+        Self {
+            rest: chain,
+            model,
+            commands,
+            update,
+        }
+    }
+
+    /// Passes the message to the next middleware, or to the root model if this is the end of
+    /// the chain.
+    pub fn run(&mut self, message: <A::RootModel as Model>::Message) {
+        match self.rest.split_first_mut() {
+            Some((middleware, rest)) => {
+                let command = middleware.process(
+                    self.model.reader(),
+                    message,
+                    Next {
+                        rest,
+                        model: self.model,
+                        commands: self.commands,
+                        update: self.update,
+                    },
+                );
+                self.commands.push(command);
+            }
+            None => (self.update)(message),
+        }
+    }
+}
+
+/// Deep copies of a model that can be written back into it.
+///
+/// Unlike [`Clone`], a snapshot never shares state with the original: cloning a [`Signal`] yields
+/// a handle to the same value, whereas snapshotting it yields a new, detached signal. Restoring
+/// writes the values back into the existing signals so their subscribers are notified.
+///
+/// Models implement it with `#[derive(emyu::Snapshot)]`, which snapshots every field, or clones
+/// the fields marked `#[snapshot(clone)]`.
+pub trait Snapshot {
+    fn snapshot(&self) -> Self;
+    fn restore(&mut self, snapshot: &Self);
+}
+
+impl<T: Clone> Snapshot for Signal<T> {
+    fn snapshot(&self) -> Self {
+        Signal::new_repr(self.reader().read().clone(), self.0.eq.clone())
+    }
+
+    fn restore(&mut self, snapshot: &Self) {
+        self.writer().set(snapshot.reader().read().clone());
+    }
+}
+
+impl<M: Snapshot> Snapshot for ModelBase<M> {
+    fn snapshot(&self) -> Self {
+        ModelBase::new(self.read().snapshot())
+    }
+
+    fn restore(&mut self, snapshot: &Self) {
+        self.write().restore(&snapshot.read());
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize> serde::Serialize for Signal<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.read().serialize(serializer)
+    }
+}
+
+/// Deserializes into a new signal without a comparator nor derived signals. Write the value into
+/// an existing signal through [`Snapshot::restore`] to keep those.
+#[cfg(feature = "serde")]
+impl<'de, T: serde::Deserialize<'de>> serde::Deserialize<'de> for Signal<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(Signal::new)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<M: serde::Serialize> serde::Serialize for ModelBase<M> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.read().serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, M: serde::Deserialize<'de>> serde::Deserialize<'de> for ModelBase<M> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        M::deserialize(deserializer).map(ModelBase::new)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum SignalStatus {
     Changed,
     Destroyed,
@@ -209,10 +438,29 @@ pub struct Signal<T>(Shared<SignalRepr<T>>);
 
 impl<T> Signal<T> {
     pub fn new(value: T) -> Self {
+        Self::new_repr(value, None)
+    }
+
+    /// Creates a signal that is only marked as changed when a written value differs from the
+    /// current one according to the given comparator, which returns `true` for equal values.
+    ///
+    /// The comparator is used by [`SignalWriter::set`] and [`SignalWriter::set_if_changed`]. Pass
+    /// [`PartialEq::eq`] to compare the values directly.
+    pub fn new_with_eq(value: T, eq: impl Fn(&T, &T) -> bool + MaybeSendSync + 'static) -> Self {
+        Self::new_repr(value, Some(Shared::new(eq)))
+    }
+
+    fn new_repr(value: T, eq: Option<Comparator<T>>) -> Self {
         Self(Shared::new(SignalRepr {
             data: Shared::new(MaybeRwLock::new(value)),
             subscribers: MaybeMutex::new(Vec::new()),
+            value_subscribers: MaybeMutex::new(Vec::new()),
             dirty: AtomicBool::new(false),
+            generation: Shared::new(AtomicU64::new(0)),
+            commits: AtomicU64::new(0),
+            dependents: MaybeMutex::new(Vec::new()),
+            derivation: None,
+            eq,
         }))
     }
 
@@ -221,11 +469,55 @@ impl<T> Signal<T> {
         let mut subscribers = self.0.subscribers.lock();
         subscribers.push(status_tx);
         SignalSubscriber {
-            reader: self.reader(),
+            data: Shared::clone(&self.0.data),
+            derivation: self.0.derivation.as_ref().map(Shared::downgrade),
+            generation: Shared::clone(&self.0.generation),
             status_rx,
+            seen: self.generation(),
         }
     }
 
+    /// Subscribes to snapshots of the value taken every time the signal is flushed as changed,
+    /// starting with the current value.
+    ///
+    /// Unlike [`Signal::subscribe`], no change is ever dropped, and every snapshot is the value
+    /// that caused the notification rather than the live one.
+    pub fn subscribe_values(&self) -> SignalValueSubscriber<T>
+    where
+        T: Clone,
+    {
+        let (value_tx, value_rx) = mpsc::unbounded();
+        let mut subscribers = self.0.value_subscribers.lock();
+        let initial = Versioned {
+            generation: self.generation(),
+            value: self.0.read().clone(),
+        };
+        value_tx.unbounded_send(initial).ok();
+        subscribers.push((Clone::clone, value_tx));
+        SignalValueSubscriber { value_rx }
+    }
+
+    /// Returns a stream of the values of this signal, starting with the current one.
+    ///
+    /// A value is yielded every time the signal is flushed as changed, and the stream ends once
+    /// the signal is dropped.
+    pub fn watch(&self) -> impl Stream<Item = T> + MaybeSend + Unpin + 'static
+    where
+        T: Clone + MaybeSend + 'static,
+    {
+        self.subscribe_values().map(|versioned| versioned.value)
+    }
+
+    /// Returns the amount of times the signal was flushed as changed.
+    pub fn generation(&self) -> u64 {
+        self.0.generation.load(Ordering::Acquire)
+    }
+
+    /// Returns `true` if the signal was flushed as changed since the given generation.
+    pub fn changed_since(&self, generation: u64) -> bool {
+        self.generation() > generation
+    }
+
     pub fn reader(&self) -> SignalReader<T> {
         SignalReader(self.clone())
     }
@@ -234,6 +526,11 @@ impl<T> Signal<T> {
         SignalWriter(self.clone())
     }
 
+    /// Returns the identity of this signal, shared by its clones.
+    pub fn id(&self) -> SignalId {
+        SignalId::of(Shared::as_ptr(&self.0))
+    }
+
     #[doc(hidden)]
     pub fn __to_dyn_flush_signals(&self, _: __private::Token) -> Shared<dyn FlushSignals>
     where
@@ -255,57 +552,252 @@ impl<T: Default> Default for Signal<T> {
     }
 }
 
-impl<T> Drop for Signal<T> {
-    fn drop(&mut self) {
-        for subscriber in &mut *self.0.subscribers.lock() {
-            subscriber.try_send(SignalStatus::Destroyed).ok();
-        }
-    }
-}
+type Comparator<T> = Shared<dyn_Maybe!(SendSync Fn(&T, &T) -> bool)>;
+
+// the clone function is captured when subscribing, so that only value subscribers require `Clone`
+type ValueSender<T> = (fn(&T) -> T, mpsc::UnboundedSender<Versioned<T>>);
 
 struct SignalRepr<T> {
     data: Shared<MaybeRwLock<T>>,
     subscribers: MaybeMutex<Vec<mpsc::Sender<SignalStatus>>>,
+    value_subscribers: MaybeMutex<Vec<ValueSender<T>>>,
     dirty: AtomicBool,
+    // incremented every time the signal is flushed as changed, shared with the subscribers so that
+    // they do not keep the signal alive
+    generation: Shared<AtomicU64>,
+    // incremented by every transaction committing to the signal, so that consistent reads can tell
+    // whether one committed while they ran
+    commits: AtomicU64,
+    // derived signals that read this one, outdated whenever this one is flushed as changed
+    dependents: MaybeMutex<Vec<MaybeWeak<dyn derived::Dependent>>>,
+    // keeps the computation of a derived signal alive for as long as the signal is
+    derivation: Option<Shared<dyn derived::Dependent>>,
+    // returns `true` if two values are equal, in which case writing one over the other is not a
+    // change
+    eq: Option<Comparator<T>>,
+}
+
+impl<T> SignalRepr<T> {
+    /// Reads the value, recomputing it first if the signal is derived and outdated.
+    fn read(&self) -> MaybeRwLockReadGuard<'_, T> {
+        if let Some(derivation) = &self.derivation {
+            derivation.refresh();
+        }
+        self.data.read()
+    }
+
+    /// Bumps the generation and tells the subscribers that the signal changed.
+    fn notify(&self) {
+        let generation = self.generation.fetch_add(1, Ordering::AcqRel) + 1;
+        {
+            let mut value_subscribers = self.value_subscribers.lock();
+            if !value_subscribers.is_empty() {
+                let data = self.read();
+                for (clone, subscriber) in &*value_subscribers {
+                    let value = clone(&data);
+                    subscriber
+                        .unbounded_send(Versioned { generation, value })
+                        .ok();
+                }
+            }
+            value_subscribers.retain(|(_, s)| !s.is_closed());
+        }
+        let mut subscribers = self.subscribers.lock();
+        for subscriber in &mut *subscribers {
+            subscriber.try_send(SignalStatus::Changed).ok();
+        }
+        subscribers.retain(|s| !s.is_closed());
+    }
+
+    /// Tells the subscribers that the signal was destroyed, then drops them so that their streams
+    /// end. The signals derived from this one are destroyed as well.
+    fn destroy(&self) {
+        for subscriber in core::mem::take(&mut *self.subscribers.lock()) {
+            // every sender is guaranteed a slot of its own, so sending through a fresh clone
+            // cannot fail because of pending changes
+            subscriber.clone().try_send(SignalStatus::Destroyed).ok();
+        }
+        self.value_subscribers.lock().clear();
+        for dependent in self.live_dependents() {
+            dependent.destroy();
+        }
+    }
+
+    /// Replaces the value, marking the signal as dirty unless its comparator considers both values
+    /// equal. Returns whether the value was replaced.
+    fn replace(&self, value: T) -> bool {
+        match &self.eq {
+            Some(eq) => self.replace_with(value, |a, b| eq(a, b)),
+            None => self.replace_with(value, |_, _| false),
+        }
+    }
+
+    fn replace_with(&self, value: T, eq: impl Fn(&T, &T) -> bool) -> bool {
+        let mut data = self.data.write();
+        if eq(&data, &value) {
+            return false;
+        }
+        *data = value;
+        self.dirty.store(true, Ordering::Release);
+        true
+    }
+}
+
+impl<T> Drop for SignalRepr<T> {
+    fn drop(&mut self) {
+        self.destroy();
+    }
+}
+
+/// A model field that a getter can expose: a [`Signal`], or a collection wrapping one.
+#[doc(hidden)]
+pub trait GetterField: Clone {
+    type Data: MaybeSendStatic;
+
+    fn __signal(&self, _token: __private::Token) -> Signal<Self::Data>;
+}
+
+impl<T: MaybeSendStatic> GetterField for Signal<T> {
+    type Data = T;
+
+    fn __signal(&self, _: __private::Token) -> Signal<T> {
+        self.clone()
+    }
+}
+
+/// Identifies a signal, see [`Signal::id`].
+///
+/// Only unique among the signals that are alive: the id of a dropped signal may be reused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SignalId(usize);
+
+impl SignalId {
+    fn of<T>(repr: *const SignalRepr<T>) -> Self {
+        Self(repr as *const () as usize)
+    }
 }
 
 #[doc(hidden)]
 pub trait FlushSignals: MaybeSendSync {
-    fn __flush(&self, _token: __private::Token);
+    /// Notifies the subscribers if the signal was written to, returning whether it was.
+    ///
+    /// Every signal that changed as a result, starting with this one and followed by the signals
+    /// derived from it, is passed to `changed`.
+    fn __flush_with(&self, changed: &mut dyn FnMut(SignalId), _token: __private::Token) -> bool;
+
+    /// Like [`FlushSignals::__flush_with`], without reporting the signals that changed.
+    fn __flush(&self, token: __private::Token) -> bool {
+        self.__flush_with(&mut |_| {}, token)
+    }
+
+    /// Notifies the subscribers that the signal was destroyed, then drops them.
+    fn __destroy(&self, _token: __private::Token);
 }
 
 impl<T: MaybeSendSync> FlushSignals for SignalRepr<T> {
-    fn __flush(&self, _: __private::Token) {
-        if self.dirty.swap(false, Ordering::AcqRel) {
-            let mut subscribers = self.subscribers.lock();
-            for subscriber in &mut *subscribers {
-                subscriber.try_send(SignalStatus::Changed).ok();
-            }
-            subscribers.retain(|s| !s.is_closed());
+    fn __flush_with(&self, changed: &mut dyn FnMut(SignalId), _: __private::Token) -> bool {
+        if !self.dirty.swap(false, Ordering::AcqRel) {
+            return false;
         }
+        self.notify();
+        changed(SignalId::of(self));
+        self.invalidate_dependents(changed);
+        true
+    }
+
+    fn __destroy(&self, _: __private::Token) {
+        self.destroy();
     }
 }
 
 impl<T: MaybeSendSync> FlushSignals for Vec<Signal<T>> {
-    fn __flush(&self, _: __private::Token) {
+    fn __flush_with(&self, changed: &mut dyn FnMut(SignalId), _: __private::Token) -> bool {
+        self.iter().fold(false, |flushed, signal| {
+            signal.0.__flush_with(changed, crate::__token()) | flushed
+        })
+    }
+
+    fn __destroy(&self, _: __private::Token) {
         for signal in self {
-            signal.0.__flush(crate::__token());
+            signal.0.destroy();
         }
     }
 }
 
+/// Receives the status of a signal.
+///
+/// A subscriber does not keep the signal alive: once every handle to it is dropped, or the host
+/// shuts down, it receives [`SignalStatus::Destroyed`] and its stream ends.
 pub struct SignalSubscriber<T> {
-    reader: SignalReader<T>,
+    data: Shared<MaybeRwLock<T>>,
+    derivation: Option<MaybeWeak<dyn derived::Dependent>>,
+    generation: Shared<AtomicU64>,
     status_rx: mpsc::Receiver<SignalStatus>,
+    seen: u64,
 }
 
 impl<T> SignalSubscriber<T> {
     pub fn read(&self) -> MaybeRwLockReadGuard<'_, T> {
-        self.reader.read()
+        if let Some(derivation) = self.derivation.as_ref().and_then(MaybeWeak::upgrade) {
+            derivation.refresh();
+        }
+        self.data.read()
     }
 
     pub async fn recv_status(&mut self) -> Option<SignalStatus> {
-        self.status_rx.next().await
+        self.next().await
+    }
+
+    /// Returns the generation of the signal when the last status was received, or when this
+    /// subscriber was created if none was.
+    pub fn generation(&self) -> u64 {
+        self.seen
+    }
+
+    /// Returns `true` if the signal was flushed as changed since the given generation.
+    ///
+    /// Notifications are coalesced when the subscriber lags behind, so comparing generations is
+    /// the reliable way to tell whether anything was missed.
+    pub fn changed_since(&self, generation: u64) -> bool {
+        self.generation.load(Ordering::Acquire) > generation
+    }
+}
+
+impl<T> Stream for SignalSubscriber<T> {
+    type Item = SignalStatus;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let status = self.status_rx.poll_next_unpin(cx);
+        if let Poll::Ready(Some(_)) = status {
+            self.seen = self.generation.load(Ordering::Acquire);
+        }
+        status
+    }
+}
+
+/// A value of a signal along with the generation it was flushed with.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Versioned<T> {
+    pub generation: u64,
+    pub value: T,
+}
+
+/// Receives snapshots of the value of a signal, created through [`Signal::subscribe_values`].
+pub struct SignalValueSubscriber<T> {
+    value_rx: mpsc::UnboundedReceiver<Versioned<T>>,
+}
+
+impl<T> SignalValueSubscriber<T> {
+    pub async fn recv_value(&mut self) -> Option<Versioned<T>> {
+        self.value_rx.next().await
+    }
+}
+
+impl<T> Stream for SignalValueSubscriber<T> {
+    type Item = Versioned<T>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.value_rx.poll_next_unpin(cx)
     }
 }
 
@@ -313,7 +805,7 @@ pub struct SignalReader<T>(Signal<T>);
 
 impl<T> SignalReader<T> {
     pub fn read(&self) -> MaybeRwLockReadGuard<'_, T> {
-        self.0.0.data.read()
+        self.0.0.read()
     }
 }
 
@@ -336,8 +828,24 @@ impl<T> SignalWriter<T> {
         ret
     }
 
+    /// Sets the value, marking the signal as changed unless it was created with a comparator
+    /// through [`Signal::new_with_eq`] that considers the new value equal to the current one.
     pub fn set(&self, value: T) {
-        self.update(|data| *data = value);
+        self.0.0.replace(value);
+    }
+
+    /// Sets the value only if it differs from the current one, returning whether it did.
+    ///
+    /// Uses the comparator of the signal if it has one, and [`PartialEq`] otherwise.
+    pub fn set_if_changed(&self, value: T) -> bool
+    where
+        T: PartialEq,
+    {
+        let repr = &self.0.0;
+        match &repr.eq {
+            Some(_) => repr.replace(value),
+            None => repr.replace_with(value, PartialEq::eq),
+        }
     }
 }
 
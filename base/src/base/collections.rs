@@ -0,0 +1,576 @@
+use super::{
+    FlushSignals, GetterField, Signal, SignalId, SignalReader, SignalSubscriber, Snapshot,
+};
+use crate::__private;
+use crate::maybe::{MaybeMutex, MaybeSendStatic, MaybeSendSync, Shared};
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::mem;
+use core::pin::Pin;
+use core::sync::atomic::Ordering;
+use core::task::{Context, Poll};
+use futures::channel::mpsc;
+use futures::{Stream, StreamExt};
+
+/// A change made to a [`SignalVec`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VecDiff<T> {
+    /// The whole vector was replaced. Always the first diff received by a subscriber.
+    Replace {
+        values: Vec<T>,
+    },
+    InsertAt {
+        index: usize,
+        value: T,
+    },
+    UpdateAt {
+        index: usize,
+        value: T,
+    },
+    RemoveAt {
+        index: usize,
+    },
+    Push {
+        value: T,
+    },
+    Pop,
+    Clear,
+}
+
+/// A change made to a [`SignalMap`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MapDiff<K, V> {
+    /// The whole map was replaced. Always the first diff received by a subscriber.
+    Replace {
+        entries: Vec<(K, V)>,
+    },
+    Insert {
+        key: K,
+        value: V,
+    },
+    Update {
+        key: K,
+        value: V,
+    },
+    Remove {
+        key: K,
+    },
+    Clear,
+}
+
+struct Diffs<D> {
+    pending: Vec<D>,
+    // (amount of pending diffs already contained in the subscriber's initial snapshot, sender)
+    subscribers: Vec<(usize, mpsc::UnboundedSender<D>)>,
+}
+
+impl<D: Clone> Diffs<D> {
+    fn subscribe(&mut self, initial: D) -> DiffSubscriber<D> {
+        let (diff_tx, diff_rx) = mpsc::unbounded();
+        diff_tx.unbounded_send(initial).ok();
+        self.subscribers.push((self.pending.len(), diff_tx));
+        DiffSubscriber { diff_rx }
+    }
+
+    fn flush(&mut self) {
+        let pending = mem::take(&mut self.pending);
+        for (skip, subscriber) in &mut self.subscribers {
+            for diff in pending.iter().skip(mem::take(skip)) {
+                subscriber.unbounded_send(diff.clone()).ok();
+            }
+        }
+        self.subscribers.retain(|(_, s)| !s.is_closed());
+    }
+}
+
+impl<D> Default for Diffs<D> {
+    fn default() -> Self {
+        Self {
+            pending: Vec::new(),
+            subscribers: Vec::new(),
+        }
+    }
+}
+
+/// Receives the changes made to a [`SignalVec`] or [`SignalMap`], in order.
+///
+/// Unlike [`super::SignalSubscriber`], no change is ever dropped, no matter how slow the
+/// subscriber is.
+pub struct DiffSubscriber<D> {
+    diff_rx: mpsc::UnboundedReceiver<D>,
+}
+
+impl<D> DiffSubscriber<D> {
+    pub async fn recv_diff(&mut self) -> Option<D> {
+        self.diff_rx.next().await
+    }
+}
+
+impl<D> Stream for DiffSubscriber<D> {
+    type Item = D;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.diff_rx.poll_next_unpin(cx)
+    }
+}
+
+struct CollectionRepr<C, D> {
+    signal: Signal<C>,
+    diffs: MaybeMutex<Diffs<D>>,
+}
+
+impl<C: MaybeSendSync, D: Clone + MaybeSendSync> FlushSignals for CollectionRepr<C, D> {
+    fn __flush_with(&self, changed: &mut dyn FnMut(SignalId), token: __private::Token) -> bool {
+        self.diffs.lock().flush();
+        self.signal.0.__flush_with(changed, token)
+    }
+
+    fn __destroy(&self, token: __private::Token) {
+        self.diffs.lock().subscribers.clear();
+        self.signal.0.__destroy(token)
+    }
+}
+
+impl<C, D> CollectionRepr<C, D> {
+    fn new(value: C) -> Self {
+        Self {
+            signal: Signal::new(value),
+            diffs: MaybeMutex::new(Diffs::default()),
+        }
+    }
+
+    /// Runs `f` on the data, marking the collection as changed only if `f` recorded a diff.
+    fn update<R>(&self, f: impl FnOnce(&mut C, &mut Vec<D>) -> R) -> R {
+        // the diffs are locked first so that a concurrent subscriber never observes the data
+        // without the matching diff
+        let mut diffs = self.diffs.lock();
+        let pending = diffs.pending.len();
+        let ret = f(&mut self.signal.0.data.write(), &mut diffs.pending);
+        if diffs.pending.len() > pending {
+            self.signal.0.dirty.store(true, Ordering::Release);
+        }
+        ret
+    }
+}
+
+/// A [`Signal`] of a [`Vec`] whose subscribers can receive fine-grained [`VecDiff`]s.
+///
+/// Like a signal, it can be a field of a model exposed through a getter returning it, such as
+/// `fn items(&self) -> SignalVec<Item>;`, so that the host flushes it after every update.
+pub struct SignalVec<T>(Shared<CollectionRepr<Vec<T>, VecDiff<T>>>);
+
+impl<T: Clone> SignalVec<T> {
+    pub fn new(values: Vec<T>) -> Self {
+        Self(Shared::new(CollectionRepr::new(values)))
+    }
+
+    /// Subscribes to the changes made to this vector, starting with its current contents.
+    pub fn subscribe_diffs(&self) -> DiffSubscriber<VecDiff<T>> {
+        let mut diffs = self.0.diffs.lock();
+        let values = self.0.signal.0.data.read().clone();
+        diffs.subscribe(VecDiff::Replace { values })
+    }
+}
+
+/// Writes to a [`SignalVec`], recording a [`VecDiff`] for every change.
+pub struct SignalVecWriter<T>(SignalVec<T>);
+
+impl<T: Clone> SignalVecWriter<T> {
+    pub fn push(&self, value: T) {
+        self.0.0.update(|values, diffs| {
+            diffs.push(VecDiff::Push {
+                value: value.clone(),
+            });
+            values.push(value);
+        })
+    }
+
+    pub fn pop(&self) -> Option<T> {
+        self.0.0.update(|values, diffs| {
+            let value = values.pop()?;
+            diffs.push(VecDiff::Pop);
+            Some(value)
+        })
+    }
+
+    pub fn insert(&self, index: usize, value: T) {
+        self.0.0.update(|values, diffs| {
+            values.insert(index, value.clone());
+            diffs.push(VecDiff::InsertAt { index, value });
+        })
+    }
+
+    pub fn set(&self, index: usize, value: T) {
+        self.0.0.update(|values, diffs| {
+            values[index] = value.clone();
+            diffs.push(VecDiff::UpdateAt { index, value });
+        })
+    }
+
+    /// Updates the value at the given index in place, recording the result as a single
+    /// [`VecDiff::UpdateAt`].
+    pub fn update_at<R>(&self, index: usize, f: impl FnOnce(&mut T) -> R) -> R {
+        self.0.0.update(|values, diffs| {
+            let ret = f(&mut values[index]);
+            diffs.push(VecDiff::UpdateAt {
+                index,
+                value: values[index].clone(),
+            });
+            ret
+        })
+    }
+
+    pub fn remove(&self, index: usize) -> T {
+        self.0.0.update(|values, diffs| {
+            let value = values.remove(index);
+            diffs.push(VecDiff::RemoveAt { index });
+            value
+        })
+    }
+
+    pub fn clear(&self) {
+        self.0.0.update(|values, diffs| {
+            values.clear();
+            diffs.push(VecDiff::Clear);
+        })
+    }
+
+    pub fn replace(&self, new_values: Vec<T>) {
+        self.0.0.update(|values, diffs| {
+            diffs.push(VecDiff::Replace {
+                values: new_values.clone(),
+            });
+            *values = new_values;
+        })
+    }
+}
+
+impl<T> SignalVec<T> {
+    /// Subscribes to the status of the vector, which only tells that it changed. Use
+    /// [`SignalVec::subscribe_diffs`] to find out how.
+    pub fn subscribe(&self) -> SignalSubscriber<Vec<T>> {
+        self.0.signal.subscribe()
+    }
+
+    /// Returns the amount of times the vector was flushed as changed.
+    pub fn generation(&self) -> u64 {
+        self.0.signal.generation()
+    }
+
+    /// Returns `true` if the vector was flushed as changed since the given generation.
+    pub fn changed_since(&self, generation: u64) -> bool {
+        self.0.signal.changed_since(generation)
+    }
+
+    pub fn reader(&self) -> SignalReader<Vec<T>> {
+        self.0.signal.reader()
+    }
+
+    pub fn writer(&self) -> SignalVecWriter<T> {
+        SignalVecWriter(self.clone())
+    }
+
+    #[doc(hidden)]
+    pub fn __to_dyn_flush_signals(&self, _: __private::Token) -> Shared<dyn FlushSignals>
+    where
+        T: Clone + MaybeSendSync + 'static,
+    {
+        Shared::clone(&self.0) as _
+    }
+}
+
+impl<T: MaybeSendStatic> GetterField for SignalVec<T> {
+    type Data = Vec<T>;
+
+    fn __signal(&self, _: __private::Token) -> Signal<Vec<T>> {
+        self.0.signal.clone()
+    }
+}
+
+impl<T> Clone for SignalVec<T> {
+    fn clone(&self) -> Self {
+        Self(Shared::clone(&self.0))
+    }
+}
+
+impl<T> Clone for SignalVecWriter<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T: Clone> Default for SignalVec<T> {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl<T: Clone> Snapshot for SignalVec<T> {
+    fn snapshot(&self) -> Self {
+        Self::new(self.reader().read().clone())
+    }
+
+    fn restore(&mut self, snapshot: &Self) {
+        self.writer().replace(snapshot.reader().read().clone());
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize> serde::Serialize for SignalVec<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.reader().read().serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: Clone + serde::Deserialize<'de>> serde::Deserialize<'de> for SignalVec<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::deserialize(deserializer).map(Self::new)
+    }
+}
+
+/// A [`Signal`] of a [`BTreeMap`] whose subscribers can receive fine-grained [`MapDiff`]s.
+///
+/// It can be exposed through a getter just like a [`SignalVec`].
+pub struct SignalMap<K, V>(Shared<CollectionRepr<BTreeMap<K, V>, MapDiff<K, V>>>);
+
+impl<K: Ord + Clone, V: Clone> SignalMap<K, V> {
+    pub fn new(entries: BTreeMap<K, V>) -> Self {
+        Self(Shared::new(CollectionRepr::new(entries)))
+    }
+
+    /// Subscribes to the changes made to this map, starting with its current contents.
+    pub fn subscribe_diffs(&self) -> DiffSubscriber<MapDiff<K, V>> {
+        let mut diffs = self.0.diffs.lock();
+        let entries = self
+            .0
+            .signal
+            .0
+            .data
+            .read()
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        diffs.subscribe(MapDiff::Replace { entries })
+    }
+}
+
+/// Writes to a [`SignalMap`], recording a [`MapDiff`] for every change.
+pub struct SignalMapWriter<K, V>(SignalMap<K, V>);
+
+impl<K: Ord + Clone, V: Clone> SignalMapWriter<K, V> {
+    /// Inserts the given entry, returning the value it replaced, if any.
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        self.0.0.update(|entries, diffs| {
+            let old = entries.insert(key.clone(), value.clone());
+            diffs.push(match old {
+                Some(_) => MapDiff::Update { key, value },
+                None => MapDiff::Insert { key, value },
+            });
+            old
+        })
+    }
+
+    /// Updates the value of the given key in place, returning `None` if it is not present.
+    pub fn update<R>(&self, key: &K, f: impl FnOnce(&mut V) -> R) -> Option<R> {
+        self.0.0.update(|entries, diffs| {
+            let value = entries.get_mut(key)?;
+            let ret = f(value);
+            diffs.push(MapDiff::Update {
+                key: key.clone(),
+                value: value.clone(),
+            });
+            Some(ret)
+        })
+    }
+
+    pub fn remove(&self, key: &K) -> Option<V> {
+        self.0.0.update(|entries, diffs| {
+            let value = entries.remove(key)?;
+            diffs.push(MapDiff::Remove { key: key.clone() });
+            Some(value)
+        })
+    }
+
+    pub fn clear(&self) {
+        self.0.0.update(|entries, diffs| {
+            entries.clear();
+            diffs.push(MapDiff::Clear);
+        })
+    }
+
+    pub fn replace(&self, new_entries: BTreeMap<K, V>) {
+        self.0.0.update(|entries, diffs| {
+            diffs.push(MapDiff::Replace {
+                entries: new_entries
+                    .iter()
+                    .map(|(k, v)| (k.clone(), v.clone()))
+                    .collect(),
+            });
+            *entries = new_entries;
+        })
+    }
+}
+
+impl<K, V> SignalMap<K, V> {
+    /// Subscribes to the status of the map, which only tells that it changed. Use
+    /// [`SignalMap::subscribe_diffs`] to find out how.
+    pub fn subscribe(&self) -> SignalSubscriber<BTreeMap<K, V>> {
+        self.0.signal.subscribe()
+    }
+
+    /// Returns the amount of times the map was flushed as changed.
+    pub fn generation(&self) -> u64 {
+        self.0.signal.generation()
+    }
+
+    /// Returns `true` if the map was flushed as changed since the given generation.
+    pub fn changed_since(&self, generation: u64) -> bool {
+        self.0.signal.changed_since(generation)
+    }
+
+    pub fn reader(&self) -> SignalReader<BTreeMap<K, V>> {
+        self.0.signal.reader()
+    }
+
+    pub fn writer(&self) -> SignalMapWriter<K, V> {
+        SignalMapWriter(self.clone())
+    }
+
+    #[doc(hidden)]
+    pub fn __to_dyn_flush_signals(&self, _: __private::Token) -> Shared<dyn FlushSignals>
+    where
+        K: Clone + MaybeSendSync + 'static,
+        V: Clone + MaybeSendSync + 'static,
+    {
+        Shared::clone(&self.0) as _
+    }
+}
+
+impl<K: MaybeSendStatic, V: MaybeSendStatic> GetterField for SignalMap<K, V> {
+    type Data = BTreeMap<K, V>;
+
+    fn __signal(&self, _: __private::Token) -> Signal<BTreeMap<K, V>> {
+        self.0.signal.clone()
+    }
+}
+
+impl<K, V> Clone for SignalMap<K, V> {
+    fn clone(&self) -> Self {
+        Self(Shared::clone(&self.0))
+    }
+}
+
+impl<K, V> Clone for SignalMapWriter<K, V> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> Default for SignalMap<K, V> {
+    fn default() -> Self {
+        Self::new(BTreeMap::new())
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> Snapshot for SignalMap<K, V> {
+    fn snapshot(&self) -> Self {
+        Self::new(self.reader().read().clone())
+    }
+
+    fn restore(&mut self, snapshot: &Self) {
+        self.writer().replace(snapshot.reader().read().clone());
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<K: serde::Serialize, V: serde::Serialize> serde::Serialize for SignalMap<K, V> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.reader().read().serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, K, V> serde::Deserialize<'de> for SignalMap<K, V>
+where
+    K: Ord + Clone + serde::Deserialize<'de>,
+    V: Clone + serde::Deserialize<'de>,
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        BTreeMap::deserialize(deserializer).map(Self::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MapDiff, SignalMap, SignalVec, VecDiff};
+    use alloc::collections::BTreeMap;
+    use alloc::vec;
+    use alloc::vec::Vec;
+    use futures::StreamExt;
+    use futures::executor::block_on;
+
+    #[test]
+    fn is_only_dirty_after_a_change() {
+        let values = SignalVec::<i32>::default();
+        let signals = values.__to_dyn_flush_signals(crate::__token());
+
+        assert_eq!(values.writer().pop(), None);
+        assert!(!signals.__flush(crate::__token()));
+
+        values.writer().push(1);
+        assert!(signals.__flush(crate::__token()));
+        assert!(!signals.__flush(crate::__token()));
+        assert_eq!(values.generation(), 1);
+    }
+
+    #[test]
+    fn sends_the_diffs_made_after_the_initial_contents() {
+        let values = SignalVec::new(vec![1]);
+        values.writer().push(2);
+        let mut diffs = values.subscribe_diffs();
+        let signals = values.__to_dyn_flush_signals(crate::__token());
+        values.writer().set(0, 3);
+        values.writer().pop();
+        assert!(signals.__flush(crate::__token()));
+
+        drop((values, signals));
+        let diffs: Vec<_> = block_on(diffs.by_ref().collect());
+        assert_eq!(
+            diffs,
+            [
+                VecDiff::Replace { values: vec![1, 2] },
+                VecDiff::UpdateAt { index: 0, value: 3 },
+                VecDiff::Pop,
+            ]
+        );
+    }
+
+    #[test]
+    fn records_updates_of_present_keys_only() {
+        let entries = SignalMap::new(BTreeMap::from([(1, "a")]));
+        let mut diffs = entries.subscribe_diffs();
+        let signals = entries.__to_dyn_flush_signals(crate::__token());
+
+        assert_eq!(entries.writer().remove(&2), None);
+        assert_eq!(entries.writer().update(&2, |_| ()), None);
+        assert!(!signals.__flush(crate::__token()));
+
+        assert_eq!(entries.writer().insert(1, "b"), Some("a"));
+        entries.writer().insert(2, "c");
+        assert!(signals.__flush(crate::__token()));
+
+        drop((entries, signals));
+        let diffs: Vec<_> = block_on(diffs.by_ref().collect());
+        assert_eq!(
+            diffs,
+            [
+                MapDiff::Replace {
+                    entries: vec![(1, "a")]
+                },
+                MapDiff::Update { key: 1, value: "b" },
+                MapDiff::Insert { key: 2, value: "c" },
+            ]
+        );
+    }
+}
@@ -0,0 +1,498 @@
+use super::{Signal, SignalId, SignalRepr};
+use crate::maybe::{
+    MaybeMutex, MaybeRwLock, MaybeRwLockReadGuard, MaybeSend, MaybeSendSync, MaybeWeak, Shared,
+};
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+pub(super) trait Dependent: MaybeSendSync {
+    /// Marks the value as outdated, so that it is recomputed the next time it is read.
+    fn invalidate(&self);
+
+    /// Recomputes the value if it is outdated.
+    fn refresh(&self);
+
+    /// Notifies the subscribers of the derived signal that it changed.
+    fn notify(&self);
+
+    fn id(&self) -> SignalId;
+
+    fn dependents(&self) -> Vec<Shared<dyn Dependent>>;
+
+    /// Destroys the derived signal after one of its sources was destroyed.
+    fn destroy(&self);
+}
+
+trait Source: MaybeSendSync {
+    fn add_dependent(&self, dependent: &MaybeWeak<dyn Dependent>);
+
+    fn remove_dependent(&self, dependent: &MaybeWeak<dyn Dependent>);
+
+    /// How many transactions committed to the signal.
+    fn commits(&self) -> u64;
+}
+
+impl<T: MaybeSendSync> Source for SignalRepr<T> {
+    fn add_dependent(&self, dependent: &MaybeWeak<dyn Dependent>) {
+        let mut dependents = self.dependents.lock();
+        if !dependents.iter().any(|d| MaybeWeak::ptr_eq(d, dependent)) {
+            dependents.push(MaybeWeak::clone(dependent));
+        }
+    }
+
+    fn remove_dependent(&self, dependent: &MaybeWeak<dyn Dependent>) {
+        self.dependents
+            .lock()
+            .retain(|d| !MaybeWeak::ptr_eq(d, dependent));
+    }
+
+    fn commits(&self) -> u64 {
+        self.commits.load(Ordering::Acquire)
+    }
+}
+
+impl<T> SignalRepr<T> {
+    pub(super) fn live_dependents(&self) -> Vec<Shared<dyn Dependent>> {
+        let mut dependents = self.dependents.lock();
+        dependents.retain(|d| d.strong_count() > 0);
+        dependents.iter().filter_map(MaybeWeak::upgrade).collect()
+    }
+
+    /// Marks every signal derived from this one as outdated, then notifies their subscribers and
+    /// passes them to `changed`.
+    ///
+    /// The whole graph is marked before anything is notified, so a derived signal read by a
+    /// subscriber is recomputed from sources that are all up to date, and a signal reached through
+    /// several paths is notified once.
+    pub(super) fn invalidate_dependents(&self, changed: &mut dyn FnMut(SignalId)) {
+        let mut outdated = Vec::new();
+        collect_outdated(self.live_dependents(), &mut outdated);
+        for dependent in outdated {
+            dependent.notify();
+            changed(dependent.id());
+        }
+    }
+}
+
+fn collect_outdated(
+    dependents: Vec<Shared<dyn Dependent>>,
+    outdated: &mut Vec<Shared<dyn Dependent>>,
+) {
+    for dependent in dependents {
+        if outdated.iter().any(|d| Shared::ptr_eq(d, &dependent)) {
+            continue;
+        }
+        dependent.invalidate();
+        let next = dependent.dependents();
+        // pushed before following the dependents, so that a cycle ends here
+        outdated.push(dependent);
+        collect_outdated(next, outdated);
+    }
+}
+
+/// Records the signals read while computing a derived signal.
+///
+/// Passed to the closure given to [`Signal::computed`] and [`super::read_consistent`].
+#[derive(Default)]
+pub struct Tracker {
+    // along with how many transactions had committed to them before they were first read
+    sources: Vec<(Shared<dyn Source>, u64)>,
+}
+
+impl Tracker {
+    /// Reads the given signal, making the derived signal depend on it.
+    pub fn read<'a, T: MaybeSendSync + 'static>(
+        &mut self,
+        signal: &'a Signal<T>,
+    ) -> MaybeRwLockReadGuard<'a, T> {
+        self.track(&signal.0)
+    }
+
+    fn track<'a, T: MaybeSendSync + 'static>(
+        &mut self,
+        repr: &'a Shared<SignalRepr<T>>,
+    ) -> MaybeRwLockReadGuard<'a, T> {
+        let source = Shared::clone(repr) as Shared<dyn Source>;
+        if !self.sources.iter().any(|(s, _)| Shared::ptr_eq(s, &source)) {
+            let commits = source.commits();
+            self.sources.push((source, commits));
+        }
+        repr.read()
+    }
+
+    /// Returns `true` if no transaction committed to the signals read since they were first read,
+    /// in which case the values read all come from between the same two commits.
+    pub(super) fn is_consistent(&self) -> bool {
+        self.sources
+            .iter()
+            .all(|(source, commits)| source.commits() == *commits)
+    }
+}
+
+/// How [`Signal::map`] and [`Signal::join`] hold their sources: a derived source has no owner
+/// but the signals derived from it, so it is kept alive, while any other source is only followed.
+enum SourceHandle<T> {
+    Derived(Shared<SignalRepr<T>>),
+    Owned(MaybeWeak<SignalRepr<T>>),
+}
+
+impl<T> SourceHandle<T> {
+    fn new(source: &Shared<SignalRepr<T>>) -> Self {
+        match source.derivation {
+            Some(_) => Self::Derived(Shared::clone(source)),
+            None => Self::Owned(Shared::downgrade(source)),
+        }
+    }
+
+    fn upgrade(&self) -> Option<Shared<SignalRepr<T>>> {
+        match self {
+            Self::Derived(source) => Some(Shared::clone(source)),
+            Self::Owned(source) => source.upgrade(),
+        }
+    }
+}
+
+struct Derived<T, F> {
+    target: MaybeWeak<SignalRepr<T>>,
+    // returns `None` once a source it holds weakly is gone
+    compute: MaybeMutex<F>,
+    // the signals read during the last computation, which the derived signal does not keep alive
+    sources: MaybeMutex<Vec<MaybeWeak<dyn Source>>>,
+    outdated: AtomicBool,
+    destroyed: AtomicBool,
+}
+
+impl<T, F> Derived<T, F>
+where
+    T: MaybeSendSync + 'static,
+    F: FnMut(&mut Tracker) -> Option<T> + MaybeSend + 'static,
+{
+    /// Depends on the signals read during the last computation, and only on those.
+    fn track_sources(&self, tracker: Tracker, this: &MaybeWeak<dyn Dependent>) {
+        let read: Vec<_> = tracker
+            .sources
+            .iter()
+            .map(|(source, _)| Shared::downgrade(source))
+            .collect();
+        let mut sources = self.sources.lock();
+        for source in &*sources {
+            if !read.iter().any(|s| MaybeWeak::ptr_eq(s, source))
+                && let Some(source) = source.upgrade()
+            {
+                source.remove_dependent(this);
+            }
+        }
+        for (source, _) in &tracker.sources {
+            source.add_dependent(this);
+        }
+        *sources = read;
+    }
+}
+
+impl<T, F> Dependent for Derived<T, F>
+where
+    T: MaybeSendSync + 'static,
+    F: FnMut(&mut Tracker) -> Option<T> + MaybeSend + 'static,
+{
+    fn invalidate(&self) {
+        self.outdated.store(true, Ordering::Release);
+    }
+
+    fn refresh(&self) {
+        if !self.outdated.load(Ordering::Acquire) || self.destroyed.load(Ordering::Acquire) {
+            return;
+        }
+        let Some(target) = self.target.upgrade() else {
+            return;
+        };
+        let mut compute = self.compute.lock();
+        // cleared before computing, so that a source changing meanwhile marks the value outdated
+        // again and the next read computes it once more. This also lets a computation read its
+        // own output without recursing.
+        if !self.outdated.swap(false, Ordering::AcqRel) {
+            return;
+        }
+        // computed again while a transaction committed to the sources meanwhile, so that the value
+        // never mixes values from before and after a commit
+        let (value, tracker) = loop {
+            let mut tracker = Tracker::default();
+            let Some(value) = compute(&mut tracker) else {
+                drop(compute);
+                self.destroy();
+                return;
+            };
+            if tracker.is_consistent() {
+                break (value, tracker);
+            }
+        };
+        *target.data.write() = value;
+        drop(compute);
+        if let Some(this) = &target.derivation {
+            self.track_sources(tracker, &Shared::downgrade(this));
+        }
+    }
+
+    fn notify(&self) {
+        if let Some(target) = self.target.upgrade() {
+            target.notify();
+        }
+    }
+
+    fn id(&self) -> SignalId {
+        SignalId::of(self.target.as_ptr())
+    }
+
+    fn dependents(&self) -> Vec<Shared<dyn Dependent>> {
+        self.target
+            .upgrade()
+            .map(|target| target.live_dependents())
+            .unwrap_or_default()
+    }
+
+    fn destroy(&self) {
+        // a derived signal reading its own output is among its own dependents
+        if self.destroyed.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        self.sources.lock().clear();
+        if let Some(target) = self.target.upgrade() {
+            target.destroy();
+        }
+    }
+}
+
+impl<T: MaybeSendSync + 'static> Signal<T> {
+    /// Creates a signal whose value is computed by the given closure.
+    ///
+    /// Every signal read through the [`Tracker`] becomes a dependency. Whenever a dependency is
+    /// flushed as changed, the derived signal is marked as outdated and its subscribers are
+    /// notified in the same pass. The value is recomputed lazily, once, the next time it is read,
+    /// so it never lags behind its sources nor mixes old and new values. The dependencies are
+    /// tracked anew on every computation, dropping those that are no longer read.
+    ///
+    /// The derived signal does not keep its dependencies alive, except through what the closure
+    /// captures. It is destroyed along with any of them, keeping its last value.
+    ///
+    /// [`Signal::map`] and [`Signal::join`] keep their sources alive when those are derived
+    /// themselves, so that chains such as `a.map(f).map(g)` last as long as their last signal.
+    pub fn computed<F>(mut f: F) -> Self
+    where
+        F: FnMut(&mut Tracker) -> T + MaybeSendSync + 'static,
+    {
+        let mut tracker = Tracker::default();
+        let value = f(&mut tracker);
+        Self::derive(value, tracker, move |tracker| Some(f(tracker)))
+    }
+
+    fn derive<F>(value: T, tracker: Tracker, compute: F) -> Self
+    where
+        F: FnMut(&mut Tracker) -> Option<T> + MaybeSendSync + 'static,
+    {
+        let repr = Shared::new_cyclic(|target| {
+            let derived = Derived {
+                target: MaybeWeak::clone(target),
+                compute: MaybeMutex::new(compute),
+                sources: MaybeMutex::new(Vec::new()),
+                outdated: AtomicBool::new(false),
+                destroyed: AtomicBool::new(false),
+            };
+            let derived = Shared::new(derived);
+            let this = Shared::downgrade(&derived) as MaybeWeak<dyn Dependent>;
+            derived.track_sources(tracker, &this);
+            SignalRepr {
+                data: Shared::new(MaybeRwLock::new(value)),
+                subscribers: MaybeMutex::new(Vec::new()),
+                value_subscribers: MaybeMutex::new(Vec::new()),
+                dirty: AtomicBool::new(false),
+                generation: Shared::new(AtomicU64::new(0)),
+                commits: AtomicU64::new(0),
+                dependents: MaybeMutex::new(Vec::new()),
+                derivation: Some(derived as Shared<dyn Dependent>),
+                eq: None,
+            }
+        });
+        Self(repr)
+    }
+
+    /// Creates a derived signal that maps the value of this one with the given closure.
+    pub fn map<U, F>(&self, f: F) -> Signal<U>
+    where
+        U: MaybeSendSync + 'static,
+        F: Fn(&T) -> U + MaybeSendSync + 'static,
+    {
+        let mut tracker = Tracker::default();
+        let value = f(&tracker.track(&self.0));
+        let source = SourceHandle::new(&self.0);
+        Signal::derive(value, tracker, move |tracker| {
+            Some(f(&tracker.track(&source.upgrade()?)))
+        })
+    }
+
+    /// Creates a derived signal that pairs the values of this signal and the given one.
+    pub fn zip<U>(&self, other: &Signal<U>) -> Signal<(T, U)>
+    where
+        T: Clone,
+        U: Clone + MaybeSendSync + 'static,
+    {
+        Signal::join((self, other))
+    }
+}
+
+/// Tuples of signals that can be joined into a single signal through [`Signal::join`].
+pub trait JoinSignals {
+    type Output;
+
+    fn join(self) -> Signal<Self::Output>;
+}
+
+macro_rules! impl_join_signals {
+    ($($T:ident $signal:ident),+) => {
+        impl<$($T),+> JoinSignals for ($(&Signal<$T>,)+)
+        where
+            $($T: Clone + MaybeSendSync + 'static),+
+        {
+            type Output = ($($T,)+);
+
+            fn join(self) -> Signal<Self::Output> {
+                let ($($signal,)+) = self;
+                let mut tracker = Tracker::default();
+                let value = ($(tracker.track(&$signal.0).clone(),)+);
+                $(let $signal = SourceHandle::new(&$signal.0);)+
+                Signal::derive(value, tracker, move |tracker| {
+                    Some(($(tracker.track(&$signal.upgrade()?).clone(),)+))
+                })
+            }
+        }
+    };
+}
+
+impl_join_signals!(A a);
+impl_join_signals!(A a, B b);
+impl_join_signals!(A a, B b, C c);
+impl_join_signals!(A a, B b, C c, D d);
+impl_join_signals!(A a, B b, C c, D d, E e);
+impl_join_signals!(A a, B b, C c, D d, E e, F f);
+impl_join_signals!(A a, B b, C c, D d, E e, F f, G g);
+impl_join_signals!(A a, B b, C c, D d, E e, F f, G g, H h);
+
+impl<T> Signal<T> {
+    /// Creates a derived signal holding the values of the given tuple of signals.
+    ///
+    /// Like every derived signal, it is recomputed after its sources are flushed instead of in a
+    /// background task, and it is destroyed along with any of them.
+    pub fn join<J: JoinSignals<Output = T>>(signals: J) -> Self {
+        signals.join()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::maybe::Shared;
+    use crate::{Signal, SignalStatus};
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use futures::StreamExt;
+    use futures::executor::block_on;
+
+    fn flush<T: crate::maybe::MaybeSendSync + 'static>(signal: &Signal<T>) {
+        signal
+            .__to_dyn_flush_signals(crate::__token())
+            .__flush(crate::__token());
+    }
+
+    fn counter() -> (Shared<AtomicUsize>, Shared<AtomicUsize>) {
+        let count = Shared::new(AtomicUsize::new(0));
+        (Shared::clone(&count), count)
+    }
+
+    #[test]
+    fn recomputes_lazily_once_per_change() {
+        let source = Signal::new(1);
+        let (runs, count) = counter();
+        let read = source.clone();
+        let doubled = Signal::computed(move |tracker| {
+            count.fetch_add(1, Ordering::Relaxed);
+            *tracker.read(&read) * 2
+        });
+        assert_eq!(runs.load(Ordering::Relaxed), 1);
+
+        source.writer().set(2);
+        flush(&source);
+        assert_eq!(runs.load(Ordering::Relaxed), 1);
+        assert_eq!(doubled.generation(), 1);
+
+        assert_eq!(*doubled.reader().read(), 4);
+        assert_eq!(*doubled.reader().read(), 4);
+        assert_eq!(runs.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn computes_a_diamond_once_from_up_to_date_sources() {
+        let source = Signal::new(1);
+        let tens = source.map(|value| value * 10);
+        let next = source.map(|value| value + 1);
+        let (runs, count) = counter();
+        let (read_tens, read_next) = (tens.clone(), next.clone());
+        let pair = Signal::computed(move |tracker| {
+            count.fetch_add(1, Ordering::Relaxed);
+            (*tracker.read(&read_tens), *tracker.read(&read_next))
+        });
+
+        source.writer().set(2);
+        flush(&source);
+        assert_eq!(pair.generation(), 1);
+        assert_eq!(*pair.reader().read(), (20, 3));
+        assert_eq!(runs.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn stops_depending_on_sources_no_longer_read() {
+        let flag = Signal::new(true);
+        let value = Signal::new(0);
+        let (read_flag, read_value) = (flag.clone(), value.clone());
+        let derived = Signal::computed(move |tracker| {
+            if *tracker.read(&read_flag) {
+                *tracker.read(&read_value)
+            } else {
+                -1
+            }
+        });
+
+        flag.writer().set(false);
+        flush(&flag);
+        assert_eq!(*derived.reader().read(), -1);
+        let generation = derived.generation();
+
+        value.writer().set(5);
+        flush(&value);
+        assert_eq!(derived.generation(), generation);
+    }
+
+    #[test]
+    fn keeps_chained_derived_sources_alive() {
+        let source = Signal::new(1);
+        let chained = source.map(|value| value + 1).map(|value| value * 2);
+
+        source.writer().set(2);
+        flush(&source);
+        assert_eq!(chained.generation(), 1);
+        assert_eq!(*chained.reader().read(), 6);
+    }
+
+    #[test]
+    fn is_destroyed_with_any_source_and_keeps_its_last_value() {
+        let first = Signal::new(1);
+        let second = Signal::new(2);
+        let sum = Signal::join((&first, &second)).map(|(a, b)| a + b);
+        let mut subscriber = sum.subscribe();
+
+        drop(second);
+        assert_eq!(block_on(subscriber.next()), Some(SignalStatus::Destroyed));
+        assert_eq!(block_on(subscriber.next()), None);
+        assert_eq!(*sum.reader().read(), 3);
+
+        // the destroyed signal no longer follows the source that is left
+        first.writer().set(10);
+        flush(&first);
+        assert_eq!(*sum.reader().read(), 3);
+    }
+}
@@ -0,0 +1,351 @@
+use super::{Signal, SignalRepr, Tracker};
+use crate::maybe::{MaybeRwLockWriteGuard, Shared};
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::cell::Cell;
+use core::sync::atomic::Ordering;
+use thiserror::Error;
+
+type Write<T> = Box<dyn FnOnce(&mut T) -> bool>;
+
+/// The error returned when committing a [`SignalTransaction`] that writes to a signal the
+/// committing thread holds a guard of, such as one returned by [`crate::SignalReader::read`].
+///
+/// Waiting for such a guard would never end, so none of the staged writes is applied.
+#[derive(Error, Debug)]
+#[non_exhaustive]
+#[error("a signal written to by the transaction is borrowed by the committing thread")]
+pub struct SignalBorrowed;
+
+trait Staged {
+    fn addr(&self) -> *const ();
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn try_lock(&self) -> Option<Box<dyn Locked + '_>>;
+    fn is_held_here(&self) -> bool;
+}
+
+trait Locked {
+    fn apply(&mut self);
+}
+
+struct StagedWrites<T> {
+    repr: Shared<SignalRepr<T>>,
+    writes: Cell<Vec<Write<T>>>,
+}
+
+impl<T: 'static> Staged for StagedWrites<T> {
+    fn addr(&self) -> *const () {
+        Shared::as_ptr(&self.repr) as _
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn try_lock(&self) -> Option<Box<dyn Locked + '_>> {
+        let guard = self.repr.data.try_write()?;
+        // bumped while the signal is locked, so that a consistent read overlapping the commit
+        // sees a different count once it is done
+        self.repr.commits.fetch_add(1, Ordering::AcqRel);
+        Some(Box::new(LockedWrites {
+            repr: &self.repr,
+            guard,
+            writes: &self.writes,
+        }))
+    }
+
+    fn is_held_here(&self) -> bool {
+        self.repr.data.is_held_here()
+    }
+}
+
+struct LockedWrites<'a, T> {
+    repr: &'a SignalRepr<T>,
+    guard: MaybeRwLockWriteGuard<'a, T>,
+    writes: &'a Cell<Vec<Write<T>>>,
+}
+
+impl<T> Locked for LockedWrites<'_, T> {
+    fn apply(&mut self) {
+        let changed = self
+            .writes
+            .take()
+            .into_iter()
+            .fold(false, |changed, write| write(&mut self.guard) | changed);
+        if changed {
+            self.repr.dirty.store(true, Ordering::Release);
+        }
+    }
+}
+
+/// Stages writes to several signals and commits them at once.
+///
+/// While committing, every signal written to is locked before any write is applied, and none is
+/// unlocked before all of them were applied. Readers therefore never see some of the writes
+/// without the others:
+///
+/// - a reader that holds the guard of one signal while reading another sees either all of the old
+///   values or all of the new ones,
+/// - reads made through [`read_consistent`], or by the closure of a [`Signal::computed`], see
+///   either all of the old values or all of the new ones even when they do not overlap, as they
+///   are made again whenever a transaction committed to one of the signals in the meantime.
+///
+/// Reads that neither overlap nor go through a [`Tracker`] are independent of each other, so they
+/// can fall on both sides of a commit.
+///
+/// A commit waits until no other thread holds a guard of any of its signals. Transactions only
+/// wait for the ones sharing signals with them, and always lock their signals in the same order,
+/// so that they cannot keep each other from committing.
+///
+/// Created through [`batch`], or [`SignalTransaction::new`] and [`SignalTransaction::commit`].
+#[derive(Default)]
+pub struct SignalTransaction {
+    staged: Vec<Box<dyn Staged>>,
+}
+
+impl SignalTransaction {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stages setting the value of the given signal, honoring its comparator like
+    /// [`crate::SignalWriter::set`].
+    pub fn set<T: 'static>(&mut self, signal: &Signal<T>, value: T) -> &mut Self {
+        let eq = signal.0.eq.clone();
+        self.stage(signal, move |data| {
+            if eq.is_some_and(|eq| eq(data, &value)) {
+                return false;
+            }
+            *data = value;
+            true
+        })
+    }
+
+    /// Stages updating the value of the given signal in place.
+    pub fn update<T: 'static>(
+        &mut self,
+        signal: &Signal<T>,
+        f: impl FnOnce(&mut T) + 'static,
+    ) -> &mut Self {
+        self.stage(signal, move |data| {
+            f(data);
+            true
+        })
+    }
+
+    fn stage<T: 'static>(
+        &mut self,
+        signal: &Signal<T>,
+        write: impl FnOnce(&mut T) -> bool + 'static,
+    ) -> &mut Self {
+        let addr = Shared::as_ptr(&signal.0) as *const ();
+        let existing = self
+            .staged
+            .iter_mut()
+            .find(|staged| staged.addr() == addr)
+            .and_then(|staged| staged.as_any_mut().downcast_mut::<StagedWrites<T>>());
+        match existing {
+            Some(staged) => staged.writes.get_mut().push(Box::new(write)),
+            None => self.staged.push(Box::new(StagedWrites {
+                repr: Shared::clone(&signal.0),
+                writes: Cell::new(alloc::vec![Box::new(write) as Write<T>]),
+            })),
+        }
+        self
+    }
+
+    /// Applies every staged write. The signals still have to be flushed afterwards, which the host
+    /// does after every update.
+    ///
+    /// Fails without applying anything if the current thread holds a guard of one of the signals.
+    /// Without `std`, such a guard cannot be told apart from one held by another thread, so the
+    /// commit waits for it forever instead.
+    pub fn commit(mut self) -> Result<(), SignalBorrowed> {
+        self.staged.sort_by_key(|staged| staged.addr());
+        let mut locked = Self::lock_all(&self.staged)?;
+        for locked in &mut locked {
+            locked.apply();
+        }
+        // the signals are only unlocked once every write was applied
+        drop(locked);
+        Ok(())
+    }
+
+    fn lock_all(staged: &[Box<dyn Staged>]) -> Result<Vec<Box<dyn Locked + '_>>, SignalBorrowed> {
+        loop {
+            let mut locked = Vec::with_capacity(staged.len());
+            for staged in staged {
+                match staged.try_lock() {
+                    Some(guard) => locked.push(guard),
+                    None if staged.is_held_here() => return Err(SignalBorrowed),
+                    None => break,
+                }
+            }
+            if locked.len() == staged.len() {
+                return Ok(locked);
+            }
+
+            // another thread holds one of the signals, so release the others to let it finish.
+            // Every transaction locks its signals in the same order, so the one holding the
+            // contended signal is never waiting for one held here
+            drop(locked);
+
+            #[cfg(feature = "std")]
+            std::thread::yield_now();
+
+            #[cfg(not(feature = "std"))]
+            core::hint::spin_loop();
+        }
+    }
+}
+
+/// Runs the given closure with a new [`SignalTransaction`], then commits it.
+pub fn batch<R>(f: impl FnOnce(&mut SignalTransaction) -> R) -> Result<R, SignalBorrowed> {
+    let mut transaction = SignalTransaction::new();
+    let ret = f(&mut transaction);
+    transaction.commit()?;
+    Ok(ret)
+}
+
+/// Runs the given closure, which reads signals through the given [`Tracker`], until no
+/// [`SignalTransaction`] committed to any of them while it ran, then returns its result.
+///
+/// The closure therefore sees either all of the old values or all of the new ones of every
+/// transaction, even when it does not hold the guards of the signals at once. It may run several
+/// times, so it should only read.
+pub fn read_consistent<R>(mut f: impl FnMut(&mut Tracker) -> R) -> R {
+    loop {
+        let mut tracker = Tracker::default();
+        let ret = f(&mut tracker);
+        if tracker.is_consistent() {
+            return ret;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::batch;
+    use crate::Signal;
+    use alloc::vec;
+    use alloc::vec::Vec;
+    use core::sync::atomic::Ordering;
+
+    fn is_dirty<T>(signal: &Signal<T>) -> bool {
+        signal.0.dirty.load(Ordering::Acquire)
+    }
+
+    #[test]
+    fn applies_the_writes_to_a_signal_in_order() {
+        let values = Signal::new(Vec::new());
+        batch(|transaction| {
+            transaction
+                .update(&values, |values| values.push(1))
+                .set(&values, vec![2])
+                .update(&values, |values| values.push(3));
+        })
+        .unwrap();
+        assert_eq!(*values.reader().read(), [2, 3]);
+        assert!(is_dirty(&values));
+    }
+
+    #[test]
+    fn honors_the_comparator_of_the_signals() {
+        let unchanged = Signal::new_with_eq(1, PartialEq::eq);
+        let changed = Signal::new_with_eq(1, PartialEq::eq);
+        batch(|transaction| {
+            transaction.set(&unchanged, 1).set(&changed, 2);
+        })
+        .unwrap();
+        assert!(!is_dirty(&unchanged));
+        assert!(is_dirty(&changed));
+    }
+
+    #[cfg(all(feature = "thread-safe", feature = "std"))]
+    #[test]
+    fn readers_holding_guards_at_once_never_see_a_partial_commit() {
+        let first = Signal::new(0i64);
+        let second = Signal::new(0i64);
+        let writers: Vec<_> = (0..4)
+            .map(|_| {
+                let (first, second) = (first.clone(), second.clone());
+                std::thread::spawn(move || {
+                    for _ in 0..1000 {
+                        batch(|transaction| {
+                            transaction
+                                .update(&first, |value| *value += 1)
+                                .update(&second, |value| *value -= 1);
+                        })
+                        .unwrap();
+                    }
+                })
+            })
+            .collect();
+        let (first_reader, second_reader) = (first.reader(), second.reader());
+        let reader = std::thread::spawn(move || {
+            for _ in 0..10000 {
+                let first = first_reader.read();
+                let second = second_reader.read();
+                assert_eq!(*first + *second, 0);
+            }
+        });
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        reader.join().unwrap();
+        assert_eq!(*first.reader().read(), 4000);
+    }
+
+    #[cfg(any(not(feature = "thread-safe"), feature = "std"))]
+    #[test]
+    fn fails_without_writing_if_the_committing_thread_holds_a_guard() {
+        use super::SignalBorrowed;
+
+        let first = Signal::new(0);
+        let second = Signal::new(0);
+        let reader = second.reader();
+        let guard = reader.read();
+        let result = batch(|transaction| {
+            transaction.set(&first, 1).set(&second, 1);
+        });
+        assert!(matches!(result, Err(SignalBorrowed)));
+        drop(guard);
+        assert_eq!(*first.reader().read(), 0);
+        assert!(!is_dirty(&first));
+    }
+
+    #[cfg(all(feature = "thread-safe", feature = "std"))]
+    #[test]
+    fn consistent_reads_never_see_a_partial_commit() {
+        use super::read_consistent;
+
+        let first = Signal::new(0i64);
+        let second = Signal::new(0i64);
+        let writer = {
+            let (first, second) = (first.clone(), second.clone());
+            std::thread::spawn(move || {
+                for _ in 0..1000 {
+                    batch(|transaction| {
+                        transaction
+                            .update(&first, |value| *value += 1)
+                            .update(&second, |value| *value -= 1);
+                    })
+                    .unwrap();
+                }
+            })
+        };
+        let reader = std::thread::spawn(move || {
+            for _ in 0..10000 {
+                // each guard is dropped before the next signal is read
+                let total = read_consistent(|tracker| {
+                    let first = *tracker.read(&first);
+                    first + *tracker.read(&second)
+                });
+                assert_eq!(total, 0);
+            }
+        });
+        writer.join().unwrap();
+        reader.join().unwrap();
+    }
+}
@@ -8,19 +8,38 @@
  */
 //! Creates host commands.
 
-use crate::maybe::{MaybeLocalBoxStream, MaybeSend, boxed_stream};
-use crate::{Application, CommandContext};
+mod retry;
+
+pub use self::retry::{RetryPolicy, RetryProgress};
+
+use crate::maybe::{MaybeLocalBoxStream, MaybeSend, MaybeSendSync, boxed_stream};
+use crate::{Application, CommandContext, CommandError, CommandId, Reply};
 use alloc::boxed::Box;
 use alloc::format;
 use alloc::vec::Vec;
+use core::hash::Hash;
 use core::pin::Pin;
+use core::time::Duration;
 use core::{fmt, task};
 use futures::channel::{mpsc, oneshot};
+use futures::future::Either;
 use futures::{FutureExt, Stream, StreamExt, future, stream};
+use thiserror::Error;
 
 type CommandRepr<T, ForApp> =
     Box<dyn_Maybe!(Send FnOnce(CommandContext<ForApp>) -> MaybeLocalBoxStream<'static, T>)>;
 
+/// The error produced by a [`Command::timeout`] that did not finish in time.
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error("the command timed out")]
+pub struct TimedOut;
+
+#[derive(Hash, PartialEq, Eq)]
+struct Debounced<K>(K);
+
+#[derive(Hash, PartialEq, Eq)]
+struct Throttled<K>(K);
+
 /// A set of concurrent actions to be performed by the host.
 ///
 /// A [`Command`] _may_ produce a bunch of values of type `T`.
@@ -32,6 +51,11 @@ impl<T, ForApp: Application> Command<T, ForApp> {
         Self(None)
     }
 
+    /// Returns `true` if this [`Command`] does nothing.
+    pub fn is_none(&self) -> bool {
+        self.0.is_none()
+    }
+
     fn some_dyn<F>(f: F) -> Self
     where
         F: FnOnce(CommandContext<ForApp>) -> MaybeLocalBoxStream<'static, T> + MaybeSend + 'static,
@@ -180,6 +204,224 @@ impl<T, ForApp: Application> Command<T, ForApp> {
         self.then(|_| Command::none())
     }
 
+    /// Makes this [`Command`] cancellable under the given key.
+    ///
+    /// The host keeps track of the running command until it finishes. Cancelling the key, either
+    /// through [`Command::cancel`] or [`CommandContext::cancel`], drops its stream.
+    pub fn cancellable<K: Hash + Eq + MaybeSendSync + 'static>(self, key: K) -> Self
+    where
+        T: 'static,
+    {
+        let id = CommandId::new(key);
+        Command(self.0.map(|stream_fn| {
+            Box::new(move |ctx: CommandContext<ForApp>| {
+                let tasks = ctx.tasks.clone();
+                tasks.register(id, stream_fn(ctx))
+            }) as CommandRepr<T, ForApp>
+        }))
+    }
+
+    /// Resolves the given [`Reply`] once this [`Command`] has finished and the host has handled
+    /// every message it produced and flushed their signals, or as soon as the host has flushed the
+    /// signals of the update if it does nothing.
+    pub fn with_reply(self, reply: Reply) -> Self
+    where
+        T: MaybeSend + 'static,
+    {
+        match self.0 {
+            None => Self::some_dyn(move |_| {
+                reply.resolve();
+                boxed_stream(stream::empty())
+            }),
+            Some(stream_fn) => Self::some_dyn(move |ctx| {
+                let replies = ctx.clone();
+                // the stream is only polled past its last message once that message was queued to the host
+                let resolve = stream::once(async move { replies.reply_when_handled(reply) })
+                    .filter_map(|()| async { None });
+                boxed_stream(stream_fn(ctx).chain(resolve))
+            }),
+        }
+    }
+
+    /// Creates a [`Command`] that cancels every running [`Command`] registered under the given
+    /// key with [`Command::cancellable`].
+    ///
+    /// The cancellation takes effect as soon as the host receives this [`Command`], so it can be
+    /// [`Command::batch`]ed before a new cancellable [`Command`] with the same key to replace it.
+    pub fn cancel<K: Hash + Eq + MaybeSendSync + 'static>(key: K) -> Self
+    where
+        T: MaybeSend + 'static,
+    {
+        let id = CommandId::new(key);
+        Self::some_dyn(move |ctx| {
+            ctx.tasks.cancel_id(&id);
+            boxed_stream(stream::empty())
+        })
+    }
+
+    /// Creates a [`Command`] that produces the given value once `duration` has elapsed, as
+    /// measured by the [`crate::Timer`] of the host.
+    ///
+    /// Like every [`Command`] that waits, it fails with a [`crate::MissingTimer`] error if the
+    /// host has no timer.
+    pub fn delay(duration: Duration, value: T) -> Self
+    where
+        T: MaybeSend + 'static,
+    {
+        Self::some(move |ctx| match ctx.timer::<T>() {
+            Some(timer) => Either::Left(stream::once(timer.sleep(duration).map(|()| value))),
+            None => Either::Right(stream::empty()),
+        })
+    }
+
+    /// Creates a [`Command`] that produces a value built by `f` every `period`, as measured by the
+    /// [`crate::Timer`] of the host.
+    ///
+    /// The [`Command`] never finishes on its own, so it is usually made
+    /// [`Command::cancellable`].
+    pub fn interval(period: Duration, f: impl FnMut() -> T + MaybeSend + 'static) -> Self
+    where
+        T: MaybeSend + 'static,
+    {
+        Self::some(move |ctx| match ctx.timer::<T>() {
+            Some(timer) => Either::Left(stream::unfold(f, move |mut f| {
+                timer.sleep(period).map(move |()| {
+                    let value = f();
+                    Some((value, f))
+                })
+            })),
+            None => Either::Right(stream::empty()),
+        })
+    }
+
+    /// Creates a [`Command`] that runs the given one once `duration` has elapsed, unless another
+    /// [`Command::debounce`] with the same key is received by the host in the meantime, in which
+    /// case it is superseded by it.
+    ///
+    /// Only the waiting is superseded: a debounced [`Command`] that already started keeps running.
+    pub fn debounce<K: Hash + Eq + MaybeSendSync + 'static>(
+        key: K,
+        duration: Duration,
+        command: Self,
+    ) -> Self
+    where
+        T: MaybeSend + 'static,
+    {
+        let id = CommandId::new(Debounced(key));
+        Self::some_dyn(move |ctx| {
+            let Some(timer) = ctx.timer::<T>() else {
+                return boxed_stream(stream::empty());
+            };
+            ctx.tasks.cancel_id(&id);
+            let mut waiting = ctx
+                .tasks
+                .register(id, boxed_stream(stream::once(timer.sleep(duration))));
+            boxed_stream(
+                stream::once(async move {
+                    match (waiting.next().await, command.0) {
+                        (Some(()), Some(stream_fn)) => stream_fn(ctx),
+                        _ => boxed_stream(stream::empty()),
+                    }
+                })
+                .flatten(),
+            )
+        })
+    }
+
+    /// Creates a [`Command`] that runs the given one right away, then ignores every
+    /// [`Command::throttle`] with the same key received by the host until `duration` has elapsed.
+    pub fn throttle<K: Hash + Eq + MaybeSendSync + 'static>(
+        key: K,
+        duration: Duration,
+        command: Self,
+    ) -> Self
+    where
+        T: MaybeSend + 'static,
+    {
+        let id = CommandId::new(Throttled(key));
+        Self::some_dyn(move |ctx| {
+            if ctx.tasks.is_running_id(&id) {
+                return boxed_stream(stream::empty());
+            }
+            let Some(timer) = ctx.timer::<T>() else {
+                return boxed_stream(stream::empty());
+            };
+            let window = ctx.tasks.register(
+                id,
+                boxed_stream(stream::once(timer.sleep(duration)).filter_map(|()| async { None })),
+            );
+            match command.0 {
+                Some(stream_fn) => boxed_stream(stream::select(stream_fn(ctx), window)),
+                None => window,
+            }
+        })
+    }
+
+    /// Creates a [`Command`] that runs the given one after every [`Command::queued`] with the same
+    /// key received before it by the host has finished, so that they run one at a time.
+    ///
+    /// Queued commands are registered under the key, so cancelling it drops the whole queue.
+    pub fn queued<K: Hash + Eq + MaybeSendSync + 'static>(key: K, command: Self) -> Self
+    where
+        T: MaybeSend + 'static,
+    {
+        let id = CommandId::new(key);
+        Command(command.0.map(|stream_fn| {
+            Box::new(move |ctx: CommandContext<ForApp>| {
+                let tasks = ctx.tasks.clone();
+                let queue = ctx.clone();
+                tasks.register(id.clone(), queue.queue(id, move || stream_fn(ctx)))
+            }) as CommandRepr<T, ForApp>
+        }))
+    }
+
+    /// Creates a [`Command`] that cancels every running [`Command`] registered under the given key,
+    /// then runs the given one under it, so that the latest one replaces the others.
+    pub fn exclusive<K: Hash + Eq + MaybeSendSync + 'static>(key: K, command: Self) -> Self
+    where
+        T: MaybeSend + 'static,
+    {
+        let id = CommandId::new(key);
+        Self::some_dyn(move |ctx| {
+            ctx.tasks.cancel_id(&id);
+            match command.0 {
+                Some(stream_fn) => {
+                    let tasks = ctx.tasks.clone();
+                    tasks.register(id, stream_fn(ctx))
+                }
+                None => boxed_stream(stream::empty()),
+            }
+        })
+    }
+
+    /// Produces the outputs of this [`Command`] until it finishes or `duration` elapses, in which
+    /// case it is stopped and a final [`TimedOut`] error is produced.
+    pub fn timeout(self, duration: Duration) -> Command<Result<T, TimedOut>, ForApp>
+    where
+        T: MaybeSend + 'static,
+    {
+        Command(self.0.map(|stream_fn| {
+            Box::new(move |ctx: CommandContext<ForApp>| {
+                let Some(timer) = ctx.timer::<Result<T, TimedOut>>() else {
+                    return boxed_stream(stream::empty());
+                };
+                let deadline = timer.sleep(duration);
+                boxed_stream(stream::unfold(
+                    (stream_fn(ctx), Some(deadline)),
+                    |(mut stream, deadline)| async move {
+                        match future::select(stream.next(), deadline?).await {
+                            Either::Left((Some(output), deadline)) => {
+                                Some((Ok(output), (stream, Some(deadline))))
+                            }
+                            Either::Left((None, _)) => None,
+                            Either::Right(((), _)) => Some((Err(TimedOut), (stream, None))),
+                        }
+                    },
+                ))
+            }) as CommandRepr<Result<T, TimedOut>, ForApp>
+        }))
+    }
+
     /// Creates a new [`Command`] that runs the given [`Future`] and produces its output.
     pub fn future<F, Fut>(f: F) -> Self
     where
@@ -190,16 +432,19 @@ impl<T, ForApp: Application> Command<T, ForApp> {
     }
 
     /// Creates a new [`Command`] that runs the given [`Stream`] and produces each of its items.
+    ///
+    /// The stream counts against [`crate::HostBuilder::max_concurrent_commands`] until it ends.
     pub fn stream<F, S>(f: F) -> Self
     where
         F: FnOnce(CommandContext<ForApp>) -> S + MaybeSend + 'static,
         S: Stream<Item = T> + MaybeSend + 'static,
     {
         Self(Some(Box::new(move |ctx| {
+            let limit = ctx.clone();
             boxed_stream(
                 stream::once(yield_now())
                     .filter_map(|_| async { None })
-                    .chain(f(ctx)),
+                    .chain(limit.limit(move || f(ctx))),
             )
         })))
     }
@@ -248,6 +493,27 @@ impl<T, E, ForApp: Application> Command<Result<T, E>, ForApp> {
         self.then(move |result| result.map_or_else(|error| Command::done(Err(error)), &f))
     }
 
+    /// Produces the `Ok` values of this [`Command`], reporting its `Err` values to the
+    /// [`crate::ErrorHandler`]s of the host instead.
+    pub fn report_errors(self) -> Command<T, ForApp>
+    where
+        T: MaybeSend + 'static,
+        E: core::error::Error + MaybeSendSync + 'static,
+    {
+        Command(self.0.map(|stream_fn| {
+            Box::new(|ctx: CommandContext<ForApp>| {
+                let errors = ctx.errors.clone();
+                boxed_stream(stream_fn(ctx).filter_map(move |result| {
+                    future::ready(
+                        result
+                            .map_err(|error| errors.report(CommandError::new::<T>(error)))
+                            .ok(),
+                    )
+                }))
+            }) as CommandRepr<T, ForApp>
+        }))
+    }
+
     /// Maps the error type of this [`Command`] to a different one using the given function.
     pub fn map_err<E2>(
         self,
@@ -344,3 +610,147 @@ async fn yield_now() {
 
     YieldNow { yielded: false }.await;
 }
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::{Command, TimedOut};
+    use crate::host::fixture::{LogMessage, TestApp, log, run, timed_host};
+    use crate::{CommandErrorKind, HostBuilder, ManualTimer, MissingTimer, TestHost};
+    use core::time::Duration;
+    use futures::future;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn debounced(entry: &'static str) -> LogMessage {
+        run(move || Command::debounce("save", ms(10), Command::done(log(entry))))
+    }
+
+    fn throttled(entry: &'static str) -> LogMessage {
+        run(move || Command::throttle("click", ms(10), Command::done(log(entry))))
+    }
+
+    #[test]
+    fn delays_its_value_by_the_given_duration() {
+        let (mut host, timer) = timed_host();
+        host.send(run(|| Command::delay(ms(10), log("late"))));
+        host.advance(&timer, 9);
+        assert!(host.entries().is_empty());
+        host.advance(&timer, 1);
+        assert_eq!(host.entries(), ["late"]);
+    }
+
+    #[test]
+    fn debounce_only_runs_the_last_command_of_a_burst() {
+        let (mut host, timer) = timed_host();
+        host.send(debounced("first"));
+        host.advance(&timer, 5);
+        host.send(debounced("second"));
+        host.advance(&timer, 5);
+        assert!(host.entries().is_empty());
+        host.advance(&timer, 5);
+        assert_eq!(host.entries(), ["second"]);
+        host.send(debounced("third"));
+        host.advance(&timer, 10);
+        assert_eq!(host.entries(), ["second", "third"]);
+    }
+
+    #[test]
+    fn throttle_ignores_commands_until_the_duration_elapsed() {
+        let (mut host, timer) = timed_host();
+        host.send(throttled("first"));
+        host.send(throttled("ignored"));
+        host.advance(&timer, 9);
+        host.send(throttled("ignored"));
+        assert_eq!(host.entries(), ["first"]);
+        host.advance(&timer, 1);
+        host.send(throttled("second"));
+        assert_eq!(host.entries(), ["first", "second"]);
+    }
+
+    #[test]
+    fn timeout_stops_the_command_and_produces_an_error() {
+        let (mut host, timer) = timed_host();
+        host.send(run(|| {
+            Command::batch([
+                Command::done(log("started")),
+                Command::future(|_| future::pending()),
+            ])
+            .timeout(ms(10))
+            .map(|result| result.unwrap_or_else(|TimedOut| log("timed out")))
+        }));
+        assert_eq!(host.entries(), ["started"]);
+        host.advance(&timer, 10);
+        assert_eq!(host.entries(), ["started", "timed out"]);
+    }
+
+    #[test]
+    fn queued_commands_run_one_at_a_time_in_order() {
+        let (mut host, timer) = timed_host();
+        for (entry, millis) in [("slow", 10), ("fast", 5)] {
+            host.send(run(move || {
+                Command::queued("queue", Command::delay(ms(millis), log(entry)))
+            }));
+        }
+        host.advance(&timer, 5);
+        assert!(host.entries().is_empty());
+        host.advance(&timer, 5);
+        assert_eq!(host.entries(), ["slow"]);
+        host.advance(&timer, 5);
+        assert_eq!(host.entries(), ["slow", "fast"]);
+    }
+
+    #[test]
+    fn only_the_work_of_commands_counts_against_the_limit() {
+        let timer = ManualTimer::new();
+        let mut host = HostBuilder::defaults()
+            .timer(timer.clone())
+            .max_concurrent_commands(1)
+            .build_test();
+        host.send(run(|| {
+            Command::interval(ms(10), || log("tick")).cancellable("ticks")
+        }));
+        for (entry, millis) in [("queued", 20), ("after queued", 0)] {
+            host.send(run(move || {
+                Command::queued("queue", Command::delay(ms(millis), log(entry)))
+            }));
+        }
+        for entry in ["first", "second"] {
+            host.send(run(move || {
+                Command::perform(|ctx| ctx.sleep(ms(10)), move |_| log(entry))
+            }));
+        }
+        host.advance(&timer, 10);
+        assert_eq!(host.entries(), ["tick", "first"]);
+        host.advance(&timer, 10);
+        let mut entries = host.entries();
+        entries.sort();
+        assert_eq!(
+            entries,
+            ["after queued", "first", "queued", "second", "tick", "tick"]
+        );
+    }
+
+    #[test]
+    fn waiting_without_a_timer_reports_an_error() {
+        let mut host = TestHost::<TestApp>::defaults();
+        host.send(run(|| Command::delay(ms(10), log("never"))));
+        assert!(host.entries().is_empty());
+        let error = host.host().errors().reader().read().clone();
+        let Some(CommandErrorKind::Failed(error)) = error.as_ref().map(|error| error.kind()) else {
+            panic!("expected the command to fail, got {error:?}");
+        };
+        assert!(error.is::<MissingTimer>());
+    }
+
+    #[test]
+    fn sleeping_without_a_timer_ends_the_command() {
+        let mut host = TestHost::<TestApp>::defaults();
+        host.send(run(|| {
+            Command::perform(|ctx| ctx.sleep(ms(10)), |slept| log(format!("{slept:?}")))
+        }));
+        assert_eq!(host.entries(), ["Err(MissingTimer)"]);
+        assert!(host.host().errors().reader().read().is_some());
+    }
+}
@@ -0,0 +1,354 @@
+use super::{Command, CommandRepr};
+use crate::maybe::{MaybeSend, MaybeSendSync, boxed_stream};
+use crate::{Application, CommandContext};
+use alloc::boxed::Box;
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::time::Duration;
+use futures::{Stream, StreamExt, future, stream};
+
+type IsRetryable<E> = Box<dyn_Maybe!(SendSync Fn(&E) -> bool)>;
+
+/// Describes when and how often [`Command::retry`] tries again.
+///
+/// Retries wait for an exponentially growing delay, as measured by the [`crate::Timer`] of the
+/// host.
+pub struct RetryPolicy<E> {
+    max_attempts: u32,
+    initial_delay: Duration,
+    max_delay: Duration,
+    multiplier: f64,
+    jitter: f64,
+    is_retryable: Option<IsRetryable<E>>,
+}
+
+/// Tells that [`Command::retry_with_progress`] is about to try again.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryProgress {
+    /// The attempt about to be made, starting at 2 for the first retry.
+    pub attempt: u32,
+    pub max_attempts: u32,
+    /// How long until the attempt is made.
+    pub delay: Duration,
+}
+
+impl<E> RetryPolicy<E> {
+    /// Makes up to 3 attempts, waiting for `initial_delay` before the first retry and twice as
+    /// long before every following one.
+    pub fn exponential(initial_delay: Duration) -> Self {
+        Self {
+            max_attempts: 3,
+            initial_delay,
+            max_delay: Duration::MAX,
+            multiplier: 2.0,
+            jitter: 0.0,
+            is_retryable: None,
+        }
+    }
+
+    /// Sets the amount of attempts made, including the first one.
+    pub fn max_attempts(self, value: u32) -> Self {
+        Self {
+            max_attempts: value,
+            ..self
+        }
+    }
+
+    pub fn max_delay(self, value: Duration) -> Self {
+        Self {
+            max_delay: value,
+            ..self
+        }
+    }
+
+    /// Sets the factor applied to the delay after every retry, at least `1.0`.
+    pub fn multiplier(self, value: f64) -> Self {
+        Self {
+            multiplier: value.max(1.0),
+            ..self
+        }
+    }
+
+    /// Shortens every delay by a random fraction of up to `value`, between `0.0` and `1.0`, so that
+    /// failing clients do not all retry at once.
+    pub fn jitter(self, value: f64) -> Self {
+        Self {
+            jitter: value.clamp(0.0, 1.0),
+            ..self
+        }
+    }
+
+    /// Only retries the errors for which `f` returns `true`. Every error is retried by default.
+    pub fn retry_if(self, f: impl Fn(&E) -> bool + MaybeSendSync + 'static) -> Self {
+        Self {
+            is_retryable: Some(Box::new(f)),
+            ..self
+        }
+    }
+
+    fn is_retryable(&self, error: &E) -> bool {
+        self.is_retryable.as_ref().is_none_or(|f| f(error))
+    }
+}
+
+enum Step<T, E> {
+    Retrying(E, RetryProgress),
+    Done(Result<T, E>),
+}
+
+struct Retry<E, F, ForApp: Application> {
+    policy: RetryPolicy<E>,
+    factory: F,
+    ctx: CommandContext<ForApp>,
+    attempt: u32,
+    delay: Duration,
+    rng: u64,
+}
+
+impl<E, F, ForApp: Application> Retry<E, F, ForApp> {
+    fn next_delay(&mut self) -> Duration {
+        let delay = self.delay.min(self.policy.max_delay);
+        self.delay = scale(delay, self.policy.multiplier).min(self.policy.max_delay);
+        scale(delay, 1.0 - self.policy.jitter * self.next_random())
+    }
+
+    // splitmix64, mapped to `[0, 1)`
+    fn next_random(&mut self) -> f64 {
+        self.rng = self.rng.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+fn scale(duration: Duration, factor: f64) -> Duration {
+    Duration::try_from_secs_f64(duration.as_secs_f64() * factor).unwrap_or(Duration::MAX)
+}
+
+fn jitter_seed() -> u64 {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    let seed = NEXT.fetch_add(1, Ordering::Relaxed);
+
+    // differs between processes, so that the clients of a server do not share a sequence
+    #[cfg(feature = "std")]
+    let seed = {
+        use core::hash::{BuildHasher, Hasher};
+        let mut hasher = std::hash::RandomState::new().build_hasher();
+        hasher.write_u64(seed);
+        hasher.finish()
+    };
+
+    seed
+}
+
+impl<T, E, ForApp: Application> Command<Result<T, E>, ForApp> {
+    /// Creates a [`Command`] that runs the future built by `factory` until it succeeds, the error
+    /// is not retryable, or the policy runs out of attempts, then produces its last result.
+    ///
+    /// The host needs a [`crate::Timer`] to wait between attempts. Without one, the first error
+    /// is produced right away and a [`crate::MissingTimer`] error is reported.
+    pub fn retry<F, Fut>(policy: RetryPolicy<E>, factory: F) -> Self
+    where
+        F: FnMut(CommandContext<ForApp>) -> Fut + MaybeSend + 'static,
+        Fut: Future<Output = Result<T, E>> + MaybeSend + 'static,
+        T: MaybeSend + 'static,
+        E: MaybeSend + 'static,
+    {
+        Command(Some(Box::new(move |ctx: CommandContext<ForApp>| {
+            boxed_stream(
+                retry_steps::<Result<T, E>, _, _, _, _, _>(policy, factory, ctx).filter_map(
+                    |step| {
+                        future::ready(match step {
+                            Step::Retrying(..) => None,
+                            Step::Done(result) => Some(result),
+                        })
+                    },
+                ),
+            )
+        }) as CommandRepr<Result<T, E>, ForApp>))
+    }
+}
+
+impl<O, ForApp: Application> Command<O, ForApp> {
+    /// Like [`Command::retry`], but also produces the value returned by `on_retry` before every
+    /// retry, which is useful to show progress such as "retrying (2/5)". The last result is mapped
+    /// with `on_done`.
+    pub fn retry_with_progress<T, E, F, Fut>(
+        policy: RetryPolicy<E>,
+        factory: F,
+        mut on_retry: impl FnMut(&E, RetryProgress) -> O + MaybeSend + 'static,
+        on_done: impl FnOnce(Result<T, E>) -> O + MaybeSend + 'static,
+    ) -> Self
+    where
+        F: FnMut(CommandContext<ForApp>) -> Fut + MaybeSend + 'static,
+        Fut: Future<Output = Result<T, E>> + MaybeSend + 'static,
+        T: MaybeSend + 'static,
+        E: MaybeSend + 'static,
+        O: MaybeSend + 'static,
+    {
+        Command(Some(Box::new(move |ctx: CommandContext<ForApp>| {
+            let mut on_done = Some(on_done);
+            boxed_stream(
+                retry_steps::<O, _, _, _, _, _>(policy, factory, ctx).filter_map(move |step| {
+                    future::ready(match step {
+                        Step::Retrying(error, progress) => Some(on_retry(&error, progress)),
+                        Step::Done(result) => on_done.take().map(|on_done| on_done(result)),
+                    })
+                }),
+            )
+        }) as CommandRepr<O, ForApp>))
+    }
+}
+
+/// Runs the attempts of a retried command producing messages of type `O`.
+fn retry_steps<O, T, E, F, Fut, ForApp>(
+    policy: RetryPolicy<E>,
+    factory: F,
+    ctx: CommandContext<ForApp>,
+) -> impl Stream<Item = Step<T, E>> + MaybeSend + 'static
+where
+    F: FnMut(CommandContext<ForApp>) -> Fut + MaybeSend + 'static,
+    Fut: Future<Output = Result<T, E>> + MaybeSend + 'static,
+    T: MaybeSend + 'static,
+    E: MaybeSend + 'static,
+    ForApp: Application,
+{
+    let retry = Retry {
+        delay: policy.initial_delay,
+        policy,
+        factory,
+        ctx,
+        attempt: 0,
+        rng: jitter_seed(),
+    };
+    stream::unfold(Some((Duration::ZERO, retry)), |state| async move {
+        let (delay, mut retry) = state?;
+        // only ever delayed when the host has a timer, see below
+        if !delay.is_zero() {
+            retry.ctx.sleep(delay).await.ok();
+        }
+        retry.attempt += 1;
+        let attempt = (retry.factory)(retry.ctx.clone());
+        let error = match retry.ctx.limit_future(attempt).await {
+            Ok(value) => return Some((Step::Done(Ok(value)), None)),
+            Err(error) => error,
+        };
+        if retry.attempt >= retry.policy.max_attempts
+            || !retry.policy.is_retryable(&error)
+            || retry.ctx.timer::<O>().is_none()
+        {
+            return Some((Step::Done(Err(error)), None));
+        }
+        let delay = retry.next_delay();
+        let progress = RetryProgress {
+            attempt: retry.attempt + 1,
+            max_attempts: retry.policy.max_attempts,
+            delay,
+        };
+        Some((Step::Retrying(error, progress), Some((delay, retry))))
+    })
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::RetryPolicy;
+    use crate::host::fixture::{LogMessage, TestApp, log, run, timed_host};
+    use crate::maybe::Shared;
+    use crate::{Command, CommandErrorKind, MissingTimer, TestHost};
+    use alloc::format;
+    use core::sync::atomic::{AtomicU32, Ordering};
+    use core::time::Duration;
+    use futures::future;
+
+    /// Retries a command that fails with `errors` in turn, then succeeds with the attempt number.
+    fn retried(
+        policy: fn() -> RetryPolicy<&'static str>,
+        errors: &'static [&'static str],
+    ) -> LogMessage {
+        run(move || {
+            let attempts = Shared::new(AtomicU32::new(0));
+            Command::retry_with_progress(
+                policy(),
+                move |_| {
+                    let attempt = attempts.fetch_add(1, Ordering::Relaxed);
+                    future::ready(
+                        errors
+                            .get(attempt as usize)
+                            .map_or(Ok(attempt + 1), |e| Err(*e)),
+                    )
+                },
+                |error, progress| {
+                    log(format!(
+                        "{error}, attempt {}/{} in {:?}",
+                        progress.attempt, progress.max_attempts, progress.delay
+                    ))
+                },
+                |result| log(format!("{result:?}")),
+            )
+        })
+    }
+
+    fn exponential() -> RetryPolicy<&'static str> {
+        RetryPolicy::exponential(Duration::from_millis(10))
+    }
+
+    #[test]
+    fn waits_longer_before_every_retry_until_it_succeeds() {
+        let (mut host, timer) = timed_host();
+        host.send(retried(exponential, &["down", "down"]));
+        assert_eq!(host.entries(), ["down, attempt 2/3 in 10ms"]);
+        host.advance(&timer, 9);
+        assert_eq!(host.entries().len(), 1);
+        host.advance(&timer, 1);
+        host.advance(&timer, 20);
+        assert_eq!(
+            host.entries(),
+            [
+                "down, attempt 2/3 in 10ms",
+                "down, attempt 3/3 in 20ms",
+                "Ok(3)"
+            ]
+        );
+    }
+
+    #[test]
+    fn produces_the_last_error_once_out_of_attempts() {
+        let (mut host, timer) = timed_host();
+        host.send(retried(
+            || {
+                exponential()
+                    .max_attempts(2)
+                    .max_delay(Duration::from_millis(5))
+            },
+            &["down", "gone", "never"],
+        ));
+        host.advance(&timer, 5);
+        assert_eq!(
+            host.entries(),
+            ["down, attempt 2/2 in 5ms", "Err(\"gone\")"]
+        );
+    }
+
+    #[test]
+    fn stops_at_errors_that_are_not_retryable() {
+        let (mut host, _) = timed_host();
+        host.send(retried(
+            || exponential().retry_if(|error| *error != "fatal"),
+            &["fatal"],
+        ));
+        assert_eq!(host.entries(), ["Err(\"fatal\")"]);
+    }
+
+    #[test]
+    fn fails_at_the_first_error_without_a_timer() {
+        let mut host = TestHost::<TestApp>::defaults();
+        host.send(retried(exponential, &["down"]));
+        assert_eq!(host.entries(), ["Err(\"down\")"]);
+        let error = host.host().errors().reader().read().clone();
+        let Some(CommandErrorKind::Failed(error)) = error.as_ref().map(|error| error.kind()) else {
+            panic!("expected the retry to fail, got {error:?}");
+        };
+        assert!(error.is::<MissingTimer>());
+    }
+}
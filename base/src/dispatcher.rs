@@ -1,11 +1,12 @@
-use crate::maybe::Shared;
+use crate::maybe::{MaybeMutex, Shared};
 use crate::{
-    __private, Application, HostChannelClosed, Model, ModelBase, ModelGetterHandler,
-    ModelGetterMessage, Signal,
+    __private, Application, Error, HostChannelClosed, Model, ModelBase, ModelGetterHandler,
+    ModelGetterMessage, Signal, TrySendError,
 };
 use core::convert::identity;
+use core::fmt;
 use futures::SinkExt;
-use futures::channel::mpsc;
+use futures::channel::{mpsc, oneshot};
 
 type RootModelOf<M> = <<M as Model>::ForApp as Application>::RootModel;
 type RootMessageOf<M> = <RootModelOf<M> as Model>::Message;
@@ -37,10 +38,83 @@ impl<M: Model> Updater<M> {
             .map_err(|_| HostChannelClosed)
     }
 
+    /// Sends the message without waiting, failing if the channel to the host is full or closed.
+    ///
+    /// Meant for synchronous callers, such as FFI callbacks, that have no executor to drive
+    /// [`Updater::try_send`].
+    pub fn try_send_now(&mut self, message: M::Message) -> Result<(), TrySendError> {
+        self.tx.try_send((self.mapper)(message)).map_err(|error| {
+            if error.is_full() {
+                TrySendError::Full
+            } else {
+                TrySendError::Closed
+            }
+        })
+    }
+
+    /// Sends the message, blocking the current thread while the channel to the host is full.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called by the host, such as from an update or a command it runs, as the host
+    /// could then never make room for the message.
+    #[cfg(feature = "std")]
+    pub fn send_blocking(&mut self, message: M::Message) -> Result<(), HostChannelClosed> {
+        assert!(
+            !crate::host::is_on_host(),
+            "`Updater::send_blocking` was called by the host, which cannot make room while blocked"
+        );
+        futures::executor::block_on(self.try_send(message))
+    }
+
+    /// Sends the message without an executor, failing only if the host has stopped.
+    ///
+    /// Blocks the current thread while the channel to the host is full, like
+    /// [`Updater::send_blocking`]. Without the `std` feature, it fails with
+    /// [`TrySendError::Full`] in that case instead.
+    ///
+    /// # Panics
+    ///
+    /// Panics if it would block while called by the host, which could then never make room for
+    /// the message.
+    pub fn send_now(&mut self, message: M::Message) -> Result<(), TrySendError> {
+        match self.tx.try_send((self.mapper)(message)) {
+            Ok(()) => Ok(()),
+            #[cfg(feature = "std")]
+            Err(error) if error.is_full() => {
+                assert!(
+                    !crate::host::is_on_host(),
+                    "`Updater::send_now` was called by the host while its channel was full, which \
+                     the host cannot make room in while blocked"
+                );
+                let message = error.into_inner();
+                futures::executor::block_on(self.tx.send(message)).map_err(|_| TrySendError::Closed)
+            }
+            #[cfg(not(feature = "std"))]
+            Err(error) if error.is_full() => Err(TrySendError::Full),
+            Err(_) => Err(TrySendError::Closed),
+        }
+    }
+
+    /// Sends the message, dropping it if the host has stopped. Use [`Updater::try_send`] to find
+    /// out whether the message was delivered.
     pub async fn send(&mut self, message: M::Message) {
-        self.try_send(message)
-            .await
-            .expect("the channel to the host is closed")
+        if self.try_send(message).await.is_err() {
+            tracing::debug!("the host has stopped, dropping the message");
+        }
+    }
+
+    /// Sends the message built by `f` and waits for the host to complete the update, as signaled
+    /// through the given [`Reply`].
+    pub async fn request(&mut self, f: impl FnOnce(Reply) -> M::Message) -> Result<(), Error> {
+        let (reply, replied) = Reply::channel();
+        self.try_send(f(reply)).await?;
+        replied.await.map_err(|_| Error::UpdateDropped)
+    }
+
+    /// Returns whether the host has stopped accepting messages.
+    pub fn is_closed(&self) -> bool {
+        self.tx.is_closed()
     }
 
     pub fn zoom<Child>(self, lens: fn(<Child as Model>::Message) -> M::Message) -> Updater<Child>
@@ -68,6 +142,33 @@ impl<M: Model> Clone for Updater<M> {
     }
 }
 
+/// Carried by a message to tell its sender that the update it caused has completed.
+///
+/// Pass it to [`crate::Command::with_reply`]. Dropping every clone of a reply without resolving
+/// it makes [`Updater::request`] fail with [`Error::UpdateDropped`].
+#[derive(Clone, Default)]
+pub struct Reply(Shared<MaybeMutex<Option<oneshot::Sender<()>>>>);
+
+impl Reply {
+    pub(crate) fn channel() -> (Self, oneshot::Receiver<()>) {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        (Self(Shared::new(MaybeMutex::new(Some(reply_tx)))), reply_rx)
+    }
+
+    /// Tells the sender that the update has completed. Only the first call has an effect.
+    pub fn resolve(&self) {
+        if let Some(reply_tx) = self.0.lock().take() {
+            reply_tx.send(()).ok();
+        }
+    }
+}
+
+impl fmt::Debug for Reply {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Reply").finish_non_exhaustive()
+    }
+}
+
 pub trait WrappedUpdater: Clone + __private::Sealed {
     type Model: Model;
 
@@ -92,6 +193,11 @@ impl<M> Getter<M> {
         self.model.get()
     }
 
+    #[doc(hidden)]
+    pub fn __field<F: Clone>(&self, field: impl FnOnce(&M) -> &F, _: __private::Token) -> F {
+        field(&self.model.read()).clone()
+    }
+
     pub fn zoom<Child>(self, lens: fn(&M) -> &ModelBase<Child>) -> Getter<Child>
     where
         M: Model,
@@ -117,3 +223,28 @@ pub trait WrappedGetter: Clone + __private::Sealed {
     #[doc(hidden)]
     fn __new(getter: Getter<Self::Model>, _token: __private::Token) -> Self;
 }
+
+/// A wrapped updater and getter that can be split apart.
+#[derive(Clone)]
+pub struct Dispatcher<WU, WG> {
+    updater: WU,
+    getter: WG,
+}
+
+impl<WU: WrappedUpdater, WG: WrappedGetter> Dispatcher<WU, WG> {
+    pub fn new(updater: WU, getter: WG) -> Self {
+        Self { updater, getter }
+    }
+
+    pub fn updater(&self) -> WU {
+        self.updater.clone()
+    }
+
+    pub fn getter(&self) -> WG {
+        self.getter.clone()
+    }
+
+    pub fn split(self) -> (WU, WG) {
+        (self.updater, self.getter)
+    }
+}
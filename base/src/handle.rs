@@ -1,11 +1,13 @@
-use core::marker::PhantomData;
-use crate::{Application, GlobalFrbSpawner, GlobalTokioSpawner, Host, HostBuilder, Spawner, SpawnerExt};
-use crate::{WrappedGetter, WrappedUpdater};
+use crate::{Application, Host, HostBuilder, Spawner, WrappedGetter, WrappedUpdater};
 
-pub struct AppHandle<A: Application, WU, WG> {
-    updater: WU,
-    getter: WG,
-    _app: PhantomData<A>,
+#[cfg(feature = "frb-compat")]
+use crate::GlobalFrbSpawner;
+
+#[cfg(feature = "tokio")]
+use crate::{GlobalTokioSpawner, TokioTimer};
+
+app_handle! {
+    pub struct AppHandle;
 }
 
 impl<A, WU, WG> AppHandle<A, WU, WG>
@@ -14,42 +16,36 @@ where
     WU: WrappedUpdater<Model = A::RootModel>,
     WG: WrappedGetter<Model = A::RootModel>,
 {
-    pub fn new<S: Spawner + Default>(builder_fn: impl FnOnce(HostBuilder<A>) -> Host<A>) -> Self {
-        let host = builder_fn(HostBuilder::new());
-        let updater = host.updater();
-        let getter = host.getter();
-        S::default().spawn_detached(host.run());
-        Self {
-            updater: WU::__new(updater, crate::__token()),
-            getter: WG::__new(getter, crate::__token()),
-            _app: PhantomData,
-        }
-    }
-
-    #[cfg(feature = "frb-compat")]
-    pub fn new_frb(builder_fn: impl FnOnce(HostBuilder<A>) -> Host<A>) -> Self {
-        Self::new::<GlobalFrbSpawner>(builder_fn)
+    /// Builds the host and runs it on `S`, which is also the default spawner for its commands.
+    ///
+    /// No [`crate::Timer`] is set, since it depends on the runtime behind `S`.
+    pub fn new<S: Spawner + Default + 'static>(
+        builder_fn: impl FnOnce(HostBuilder<A>) -> Host<A>,
+    ) -> Self {
+        Self::with_builder::<S>(HostBuilder::new(), builder_fn)
     }
 
-    #[cfg(feature = "tokio")]
-    pub fn new_tokio(
+    fn with_builder<S: Spawner + Default + 'static>(
+        builder: HostBuilder<A>,
         builder_fn: impl FnOnce(HostBuilder<A>) -> Host<A>,
     ) -> Self {
-        Self::new::<GlobalTokioSpawner>(builder_fn)
+        let host = builder_fn(builder.spawner(S::default()));
+        Self::run(host, S::default())
     }
-}
 
-impl<A, WU, WG> AppHandle<A, WU, WG>
-where
-    A: Application,
-    WU: WrappedUpdater<Model = A::RootModel>,
-    WG: WrappedGetter<Model = A::RootModel>,
-{
-    pub fn updater(&self) -> WU {
-        self.updater.clone()
+    /// Runs the host on the runtime of flutter_rust_bridge. With the `tokio` feature, which that
+    /// runtime is built on outside the web, its commands get a [`TokioTimer`].
+    #[cfg(feature = "frb-compat")]
+    pub fn new_frb(builder_fn: impl FnOnce(HostBuilder<A>) -> Host<A>) -> Self {
+        let builder = HostBuilder::new();
+        #[cfg(feature = "tokio")]
+        let builder = builder.timer(TokioTimer);
+        Self::with_builder::<GlobalFrbSpawner>(builder, builder_fn)
     }
 
-    pub fn getter(&self) -> WG {
-        self.getter.clone()
+    /// Runs the host on the current tokio runtime, with a [`TokioTimer`] for its commands.
+    #[cfg(feature = "tokio")]
+    pub fn new_tokio(builder_fn: impl FnOnce(HostBuilder<A>) -> Host<A>) -> Self {
+        Self::with_builder::<GlobalTokioSpawner>(HostBuilder::new().timer(TokioTimer), builder_fn)
     }
 }
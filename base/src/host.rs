@@ -1,26 +1,106 @@
+mod error;
+mod journal;
+#[cfg(feature = "serde")]
+mod persist;
+mod scheduler;
+mod shutdown;
 mod spawner;
+mod tasks;
+#[cfg(feature = "std")]
+mod testing;
+#[cfg(all(test, feature = "std"))]
+pub(crate) use testing::fixture;
+mod timer;
 mod world;
 
-use crate::maybe::{MaybeRwLockReadGuard, MaybeSendSync, Shared};
-use crate::{Application, Model, ModelGetterHandler, ModelGetterMessage, command};
-use crate::{FlushSignals, Interceptor, ModelBase, ModelBaseReader, Signal};
-use crate::{Getter, Updater};
+use crate::maybe::{
+    MaybeLocalBoxFuture, MaybeLocalBoxStream, MaybeRwLockReadGuard, MaybeSend, MaybeSendSync,
+    Shared, boxed_future, boxed_stream,
+};
+use crate::{Application, Model, ModelGetterHandler, ModelGetterMessage, command, subscription};
+use crate::{FlushSignals, Interceptor, Middleware, ModelBase, ModelBaseReader, Next, Signal};
+use crate::{Getter, Observer, Reply, SignalId, Snapshot, Updater};
 use alloc::boxed::Box;
 use alloc::collections::VecDeque;
+use alloc::format;
+use alloc::string::String;
 use alloc::vec::Vec;
+use core::fmt::Debug;
+use core::hash::Hash;
 use core::ops::ControlFlow;
-use futures::StreamExt;
+use core::time::Duration;
+pub use error::{CommandError, CommandErrorKind, ErrorHandler, ErrorReporter};
 use futures::channel::mpsc;
+use futures::future;
+use futures::stream::{AbortHandle, Abortable};
+use futures::{FutureExt, Stream, StreamExt};
+use hashbrown::HashMap;
+pub use journal::Journal;
+use journal::JournalConfig;
+#[cfg(all(feature = "serde", feature = "std"))]
+pub use persist::FileStorage;
+#[cfg(feature = "serde")]
+pub use persist::{Persist, PersistError, Storage};
+use scheduler::{Scheduled, Scheduler, Slot};
+use shutdown::{InFlight, ShutdownReceiver, ShutdownRequest};
+pub use shutdown::{ShutdownHandle, Stopped};
 pub use spawner::*;
+pub use tasks::{CommandId, Tasks};
+#[cfg(feature = "std")]
+pub use testing::{TestEvent, TestHost, TestSpawner};
+use timer::HostTimer;
+#[cfg(feature = "tokio")]
+pub use timer::TokioTimer;
+pub use timer::{ManualTimer, MissingTimer, Timer};
 use world::WorldRepr;
 pub use world::{State, StateMut, StateRef, World};
 
 const DEFAULT_CHANNEL_BUFFER_SIZE: usize = 64;
 
+#[cfg(feature = "std")]
+std::thread_local! {
+    // set while the host runs the code of the application, which must never block on the host
+    static ON_HOST: core::cell::Cell<bool> = const { core::cell::Cell::new(false) };
+}
+
+/// Returns whether the current thread is running the code of the application on behalf of a host.
+#[cfg(feature = "std")]
+pub(crate) fn is_on_host() -> bool {
+    ON_HOST.get()
+}
+
+/// Marks the current thread as running the host until dropped. Must not be held across an
+/// `.await`, since another task could then run on the thread.
+struct OnHost {
+    #[cfg(feature = "std")]
+    was: bool,
+}
+
+impl OnHost {
+    fn enter() -> Self {
+        Self {
+            #[cfg(feature = "std")]
+            was: ON_HOST.replace(true),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Drop for OnHost {
+    fn drop(&mut self) {
+        ON_HOST.set(self.was);
+    }
+}
+
 pub struct CommandContext<A: Application> {
     pub model: ModelBaseReader<A::RootModel>,
     pub world: World,
     pub updater: Updater<A::RootModel>,
+    pub tasks: Tasks,
+    pub errors: ErrorReporter,
+    timer: HostTimer,
+    scheduler: Scheduler,
+    replies: mpsc::UnboundedSender<Reply>,
 }
 
 impl<A: Application> CommandContext<A> {
@@ -47,6 +127,72 @@ impl<A: Application> CommandContext<A> {
     pub async fn send_message(&mut self, message: <A::RootModel as Model>::Message) {
         self.updater.send(message).await
     }
+
+    pub fn cancel<K: Hash + Eq + MaybeSendSync + 'static>(&self, key: K) -> usize {
+        self.tasks.cancel(key)
+    }
+
+    /// Returns a future that resolves once `duration` has elapsed, as measured by the [`Timer`] of
+    /// the host.
+    ///
+    /// If the host was built without a [`Timer`], a [`MissingTimer`] error is reported and the
+    /// future resolves with it right away, so that the command waiting on it can end like
+    /// [`crate::Command::delay`] does.
+    pub fn sleep(
+        &self,
+        duration: Duration,
+    ) -> MaybeLocalBoxFuture<'static, Result<(), MissingTimer>> {
+        match self.timer::<RootMessage<A>>() {
+            Some(timer) => boxed_future(timer.sleep(duration).map(Ok)),
+            None => boxed_future(future::ready(Err(MissingTimer))),
+        }
+    }
+
+    /// Returns the [`Timer`] of the host, or reports a [`MissingTimer`] error on behalf of a
+    /// command producing messages of type `T` if the host was built without one.
+    pub(crate) fn timer<T>(&self) -> Option<Shared<dyn Timer>> {
+        let timer = self.timer.get().cloned();
+        if timer.is_none() {
+            self.errors.report(CommandError::new::<T>(MissingTimer));
+        }
+        timer
+    }
+
+    /// Reports an error to the error handlers of the host, on behalf of a command producing
+    /// messages of type `T`.
+    pub fn report_error<T>(&self, error: impl core::error::Error + MaybeSendSync + 'static) {
+        self.errors.report(CommandError::new::<T>(error))
+    }
+
+    /// Builds a stream with `make` once the streams queued before it under the same id are done.
+    pub(crate) fn queue<T: MaybeSend + 'static>(
+        &self,
+        id: CommandId,
+        make: impl FnOnce() -> MaybeLocalBoxStream<'static, T> + MaybeSend + 'static,
+    ) -> MaybeLocalBoxStream<'static, T> {
+        boxed_stream(self.scheduler.schedule(Slot::Keyed(id), make))
+    }
+
+    /// Builds a stream with `make` once it gets a place among the commands the host runs at once,
+    /// which it keeps until the stream is dropped.
+    pub(crate) fn limit<S: Stream, F: FnOnce() -> S>(&self, make: F) -> Scheduled<S, F> {
+        self.scheduler.schedule(Slot::Global, make)
+    }
+
+    /// Delays the given future until it gets a place among the commands the host runs at once,
+    /// which it keeps until it finishes.
+    pub(crate) async fn limit_future<F: Future>(&self, fut: F) -> F::Output {
+        self.scheduler.clone().run(Slot::Global, fut).await
+    }
+
+    /// Hands the given reply over to the host, which resolves it once it has handled every message
+    /// sent before.
+    pub(crate) fn reply_when_handled(&self, reply: Reply) {
+        if let Err(error) = self.replies.unbounded_send(reply) {
+            // the host has stopped, so there is nothing left to wait for
+            error.into_inner().resolve();
+        }
+    }
 }
 
 impl<A: Application> Clone for CommandContext<A> {
@@ -55,20 +201,51 @@ impl<A: Application> Clone for CommandContext<A> {
             model: self.model.clone(),
             world: self.world.clone(),
             updater: self.updater.clone(),
+            tasks: self.tasks.clone(),
+            errors: self.errors.clone(),
+            timer: self.timer.clone(),
+            scheduler: self.scheduler.clone(),
+            replies: self.replies.clone(),
         }
     }
 }
 
 type RootMessage<A> = <<A as Application>::RootModel as Model>::Message;
+type CloneMessage<A> = fn(&RootMessage<A>) -> RootMessage<A>;
+type DescribeMessage<A> = fn(&RootMessage<A>) -> String;
 
 pub struct Host<A: Application> {
     model: ModelBase<A::RootModel>,
     world: World,
     interceptors: Vec<Box<dyn Interceptor<A>>>,
+    middleware: Vec<Box<dyn Middleware<A>>>,
+    observers: Vec<Box<dyn Observer<A>>>,
+    error_handlers: Vec<Box<dyn ErrorHandler<A>>>,
+    clone_message: Option<CloneMessage<A>>,
+    describe_message: Option<DescribeMessage<A>>,
     spawner: Box<dyn Spawner>,
+    timer: HostTimer,
+    scheduler: Scheduler,
     signals: VecDeque<Shared<dyn FlushSignals>>,
     updater: Updater<A::RootModel>,
     message_rx: mpsc::Receiver<RootMessage<A>>,
+    // command outputs get their own channel, so that they are still accepted while draining
+    command_updater: Updater<A::RootModel>,
+    command_rx: mpsc::Receiver<RootMessage<A>>,
+    error_reporter: ErrorReporter,
+    error_rx: mpsc::UnboundedReceiver<CommandError>,
+    errors: Signal<Option<CommandError>>,
+    reply_tx: mpsc::UnboundedSender<Reply>,
+    reply_rx: mpsc::UnboundedReceiver<Reply>,
+    // replies of finished commands, resolved once the messages queued before them were handled
+    replies: Vec<Reply>,
+    tasks: Tasks,
+    in_flight: InFlight,
+    subscriptions: HashMap<CommandId, AbortHandle>,
+    journal: Option<Journal<A>>,
+    #[cfg(feature = "serde")]
+    persist: Option<Persist<A>>,
+    shutdown: ShutdownReceiver,
 }
 
 impl<A: Application> Host<A> {
@@ -91,48 +268,345 @@ impl<A: Application> Host<A> {
 impl<A: Application> Host<A> {
     pub async fn run(mut self) {
         tracing::debug!("host has started");
-        loop {
-            if let ControlFlow::Break(()) = self.run_once().await {
-                tracing::debug!("host is stopping");
-                break;
+        self.update_subscriptions();
+        let request = loop {
+            if let ControlFlow::Break(request) = self.run_once().await {
+                break request;
             }
+        };
+        tracing::debug!("host is stopping");
+        if let Some(ShutdownRequest::Drain { deadline }) = request {
+            self.drain(deadline).await;
         }
+        self.stop();
+        tracing::debug!("host has stopped");
     }
 
-    async fn run_once(&mut self) -> ControlFlow<()> {
-        match self.message_rx.next().await {
-            Some(action) => self.handle_message(action).await,
-            None => return ControlFlow::Break(()),
+    async fn run_once(&mut self) -> ControlFlow<Option<ShutdownRequest>> {
+        if let Some(request) = self.shutdown.try_recv() {
+            return ControlFlow::Break(Some(request));
+        }
+        self.handle_errors();
+        self.collect_replies();
+        let message = match self.try_next_message() {
+            Some(message) => message,
+            None => {
+                self.resolve_replies();
+                self.on_idle();
+                let mut messages =
+                    futures::stream::select(&mut self.message_rx, &mut self.command_rx);
+                futures::select! {
+                    message = messages.next() => match message {
+                        Some(message) => message,
+                        None => return ControlFlow::Break(None),
+                    },
+                    error = self.error_rx.next() => {
+                        if let Some(error) = error {
+                            self.handle_error(error);
+                        }
+                        return ControlFlow::Continue(());
+                    },
+                    reply = self.reply_rx.next() => {
+                        self.replies.extend(reply);
+                        return ControlFlow::Continue(());
+                    },
+                    request = self.shutdown.recv().fuse() => {
+                        return ControlFlow::Break(request);
+                    }
+                }
+            }
         };
+        self.handle_message(message).await;
 
         ControlFlow::Continue(())
     }
 
+    fn try_next_message(&mut self) -> Option<RootMessage<A>> {
+        self.message_rx
+            .try_recv()
+            .or_else(|_| self.command_rx.try_recv())
+            .ok()
+    }
+
+    /// Handles the queued messages and the outputs of the commands in flight until there are none
+    /// left or the deadline resolves. New messages are rejected.
+    async fn drain(&mut self, deadline: MaybeLocalBoxFuture<'static, ()>) {
+        self.message_rx.close();
+        let mut deadline = deadline.fuse();
+        loop {
+            // a command sends its last message before it stops being in flight, so checking in
+            // this order never misses one
+            let idle = self.in_flight.is_idle();
+            self.handle_errors();
+            self.collect_replies();
+            let mut handled = false;
+            while let Some(message) = self.try_next_message() {
+                self.handle_message(message).await;
+                handled = true;
+            }
+            if !handled {
+                self.resolve_replies();
+                if idle {
+                    break;
+                }
+            }
+
+            let in_flight = self.in_flight.clone();
+            let mut messages = futures::stream::select(&mut self.message_rx, &mut self.command_rx);
+            futures::select! {
+                message = messages.next() => match message {
+                    Some(message) => self.handle_message(message).await,
+                    None => break,
+                },
+                () = in_flight.idle().fuse() => {}
+                () = deadline => {
+                    tracing::warn!("host did not drain before the deadline, aborting");
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Aborts every command and subscription, saves the model and destroys its signals.
+    fn stop(&mut self) {
+        self.message_rx.close();
+        self.command_rx.close();
+        self.in_flight.abort_all();
+        for (_, handle) in self.subscriptions.drain() {
+            handle.abort();
+        }
+        self.handle_errors();
+        #[cfg(feature = "serde")]
+        if let Some(persist) = &self.persist {
+            persist.flush(&self.model);
+        }
+        self.model
+            .__accumulate_signals(&mut self.signals, crate::__token());
+        self.signals
+            .push_back(self.errors.__to_dyn_flush_signals(crate::__token()));
+        for signal in self.signals.drain(..) {
+            signal.__destroy(crate::__token());
+        }
+    }
+
+    fn handle_errors(&mut self) {
+        while let Ok(error) = self.error_rx.try_recv() {
+            self.handle_error(error);
+        }
+    }
+
+    fn handle_error(&mut self, error: CommandError) {
+        let _on_host = OnHost::enter();
+        tracing::debug!(%error, "command error");
+        for handler in &mut self.error_handlers {
+            handler.handle(self.model.reader(), &error);
+        }
+        self.errors.writer().set(Some(error));
+        self.errors
+            .__to_dyn_flush_signals(crate::__token())
+            .__flush(crate::__token());
+    }
+
+    /// Takes the replies of the commands that finished so far. A command only sends its reply after
+    /// its last message, so the messages of these commands are all queued already.
+    fn collect_replies(&mut self) {
+        while let Ok(reply) = self.reply_rx.try_recv() {
+            self.replies.push(reply);
+        }
+    }
+
+    /// Resolves the collected replies, which must only happen once no message is queued anymore.
+    /// Returns whether there were any.
+    fn resolve_replies(&mut self) -> bool {
+        let resolved = !self.replies.is_empty();
+        for reply in self.replies.drain(..) {
+            reply.resolve();
+        }
+        resolved
+    }
+
+    /// Called whenever the host runs out of queued messages.
+    fn on_idle(&mut self) {
+        #[cfg(feature = "serde")]
+        if let Some(save) = self
+            .persist
+            .as_ref()
+            .and_then(|persist| persist.save_task(&self.model, &self.timer))
+        {
+            self.spawner.spawn_detached_dyn(save);
+        }
+    }
+
     async fn handle_message(&mut self, message: RootMessage<A>) {
+        self.handle_message_with(message, |_| {}).await
+    }
+
+    async fn handle_message_with(
+        &mut self,
+        message: RootMessage<A>,
+        mut on_changed: impl FnMut(SignalId),
+    ) {
+        let _on_host = OnHost::enter();
         for interceptor in &mut self.interceptors {
             interceptor.intercept(self.model.reader(), &message);
         }
-        let command = command::into_repr(self.model.write().update(message));
+        let describe = |message: &_| self.describe_message.map(|d| Shared::from(d(message)));
+        // the commands returned by middleware are attributed to the message the host received
+        let origin = describe(&message);
+        let mut updates = Vec::new();
+        let mut commands = Vec::new();
+        let mut update = |message| {
+            if let Some(journal) = &self.journal {
+                journal.record(&message);
+            }
+            let observed = self.clone_message.map(|clone| clone(&message));
+            let origin = describe(&message);
+            let command = self.model.write().update(message);
+            if let Some(journal) = &self.journal {
+                journal.updated();
+            }
+            #[cfg(feature = "serde")]
+            if let Some(persist) = &self.persist {
+                persist.mark_dirty();
+            }
+            updates.push((observed, command, origin));
+        };
+        Next::new(
+            &mut self.middleware,
+            &self.model,
+            &mut commands,
+            &mut update,
+        )
+        .run(message);
         self.model
             .__accumulate_signals(&mut self.signals, crate::__token());
-        if let Some(command) = command {
-            let ctx = CommandContext {
-                model: self.model.reader(),
-                world: self.world.clone(),
-                updater: self.updater.clone(),
-            };
-            let mut updater = self.updater.clone();
-            self.spawner.spawn_detached(async move {
-                let mut stream = command(ctx);
-                while let Some(message) = stream.next().await {
-                    updater.send(message).await
+        self.update_subscriptions();
+        while let Some(signal) = self.signals.pop_front() {
+            signal.__flush_with(&mut on_changed, crate::__token());
+        }
+        for (message, command, _) in &updates {
+            if let Some(message) = message {
+                for observer in &mut self.observers {
+                    observer.observe(self.model.reader(), message, command);
                 }
-            });
+            }
         }
-        while let Some(signal) = self.signals.pop_front() {
-            signal.__flush(crate::__token());
+        let commands = updates
+            .into_iter()
+            .map(|(_, command, origin)| (command, origin))
+            .chain(
+                commands
+                    .into_iter()
+                    .map(|command| (command, origin.clone())),
+            );
+        for (command, origin) in commands {
+            if let Some(command) = command::into_repr(command) {
+                let errors = self.error_reporter.with_origin(origin);
+                // build the stream eagerly so that cancellations and registrations take effect
+                // in message order
+                let Some(stream) = self.build_stream(&errors, command) else {
+                    continue;
+                };
+                self.spawn_stream(errors, self.in_flight.track(stream));
+            }
         }
     }
+
+    fn update_subscriptions(&mut self) {
+        let recipes = subscription::into_recipes(self.model.read().subscriptions());
+        let mut previous = core::mem::take(&mut self.subscriptions);
+        for recipe in recipes {
+            if let Some(handle) = previous.remove(&recipe.id) {
+                self.subscriptions.insert(recipe.id, handle);
+                continue;
+            }
+            if self.subscriptions.contains_key(&recipe.id) {
+                tracing::warn!("duplicate subscription in the same batch, ignoring");
+                continue;
+            }
+            tracing::debug!(id = ?recipe.id, "starting subscription");
+            let (handle, registration) = AbortHandle::new_pair();
+            // a subscription whose closure panicked is still recorded, so that it is not retried
+            // after every update
+            if let Some(stream) = self.build_stream(&self.error_reporter, recipe.stream_fn) {
+                let errors = self.error_reporter.clone();
+                self.spawn_stream(errors, Abortable::new(stream, registration));
+            }
+            self.subscriptions.insert(recipe.id, handle);
+        }
+        for (id, handle) in previous {
+            tracing::debug!(?id, "stopping subscription");
+            handle.abort();
+        }
+    }
+
+    fn command_context(&self, errors: &ErrorReporter) -> CommandContext<A> {
+        CommandContext {
+            model: self.model.reader(),
+            world: self.world.clone(),
+            updater: self.command_updater.clone(),
+            tasks: self.tasks.clone(),
+            errors: errors.clone(),
+            timer: self.timer.clone(),
+            scheduler: self.scheduler.clone(),
+            replies: self.reply_tx.clone(),
+        }
+    }
+
+    /// Runs the closure of a command on the host, reporting a panic like one of its stream.
+    fn build_stream<S>(
+        &self,
+        errors: &ErrorReporter,
+        stream_fn: impl FnOnce(CommandContext<A>) -> S,
+    ) -> Option<S> {
+        let ctx = self.command_context(errors);
+        #[cfg(feature = "std")]
+        {
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| stream_fn(ctx)))
+                .map_err(|payload| errors.report(CommandError::panicked::<RootMessage<A>>(payload)))
+                .ok()
+        }
+        #[cfg(not(feature = "std"))]
+        {
+            Some(stream_fn(ctx))
+        }
+    }
+
+    fn spawn_stream(
+        &mut self,
+        errors: ErrorReporter,
+        stream: impl Stream<Item = RootMessage<A>> + Unpin + MaybeSend + 'static,
+    ) {
+        #[cfg(feature = "std")]
+        let mut stream = {
+            std::panic::AssertUnwindSafe(stream)
+                .catch_unwind()
+                .filter_map(move |output| {
+                    future::ready(
+                        output
+                            .map_err(|payload| {
+                                errors.report(CommandError::panicked::<RootMessage<A>>(payload))
+                            })
+                            .ok(),
+                    )
+                })
+        };
+        #[cfg(not(feature = "std"))]
+        let mut stream = {
+            // panics can only be caught with std
+            let _ = errors;
+            stream
+        };
+        let mut updater = self.command_updater.clone();
+        self.spawner.spawn_detached(async move {
+            while let Some(message) = stream.next().await {
+                if updater.try_send(message).await.is_err() {
+                    // the host has stopped
+                    break;
+                }
+            }
+        });
+    }
 }
 
 impl<A: Application> Host<A> {
@@ -143,14 +617,43 @@ impl<A: Application> Host<A> {
     pub fn getter(&self) -> Getter<A::RootModel> {
         Getter::new(self.model.clone())
     }
+
+    /// Returns a signal of the last error raised by a command.
+    pub fn errors(&self) -> Signal<Option<CommandError>> {
+        self.errors.clone()
+    }
+
+    pub fn tasks(&self) -> Tasks {
+        self.tasks.clone()
+    }
+
+    pub fn journal(&self) -> Option<Journal<A>> {
+        self.journal.clone()
+    }
+
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.handle()
+    }
 }
 
 pub struct HostBuilder<A: Application> {
     model: Option<A::RootModel>,
     world: WorldRepr,
     interceptors: Vec<Box<dyn Interceptor<A>>>,
+    middleware: Vec<Box<dyn Middleware<A>>>,
+    observers: Vec<Box<dyn Observer<A>>>,
+    error_handlers: Vec<Box<dyn ErrorHandler<A>>>,
+    clone_message: Option<CloneMessage<A>>,
+    describe_message: Option<DescribeMessage<A>>,
     spawner: Option<Box<dyn Spawner>>,
+    timer: HostTimer,
+    max_concurrent_commands: Option<usize>,
     buffer_size: usize,
+    journal: Option<JournalConfig<A>>,
+    journal_capacity: Option<usize>,
+    #[cfg(feature = "serde")]
+    persist: Option<Persist<A>>,
+    shutdown: ShutdownReceiver,
 }
 
 impl<A: Application> HostBuilder<A> {
@@ -191,6 +694,112 @@ impl<A: Application> HostBuilder<A> {
         self
     }
 
+    /// Adds a [`Middleware`] to the end of the chain. Middleware runs in registration order.
+    pub fn middleware(mut self, value: impl Middleware<A>) -> Self {
+        self.middleware.push(Box::new(value));
+        self
+    }
+
+    /// Adds an [`Observer`]. Observers run in registration order.
+    pub fn observer(mut self, value: impl Observer<A>) -> Self
+    where
+        RootMessage<A>: Clone,
+    {
+        self.observers.push(Box::new(value));
+        self.clone_message = Some(Clone::clone);
+        self
+    }
+
+    /// Describes every updated message with its [`Debug`] representation, so that the errors of the
+    /// commands returned for it can tell where they come from through [`CommandError::origin`].
+    pub fn describe_messages(self) -> Self
+    where
+        RootMessage<A>: Debug,
+    {
+        Self {
+            describe_message: Some(|message| format!("{message:?}")),
+            ..self
+        }
+    }
+
+    /// Adds an [`ErrorHandler`]. Error handlers run in registration order.
+    pub fn error_handler(mut self, value: impl ErrorHandler<A>) -> Self {
+        self.error_handlers.push(Box::new(value));
+        self
+    }
+
+    pub fn spawner(self, value: impl Spawner + 'static) -> Self {
+        Self {
+            spawner: Some(Box::new(value)),
+            ..self
+        }
+    }
+
+    /// Sets the [`Timer`] used by time-based commands, such as [`crate::Command::delay`].
+    ///
+    /// There is none by default, in which case these commands fail with a [`MissingTimer`] error.
+    pub fn timer(self, value: impl Timer) -> Self {
+        Self {
+            timer: HostTimer::new(value),
+            ..self
+        }
+    }
+
+    /// Limits how many commands do work at once, holding back the others in the order they start.
+    ///
+    /// Only the work given to [`crate::Command::future`] and [`crate::Command::stream`], which
+    /// back most other commands, and each attempt of [`crate::Command::retry`] count against the
+    /// limit, from when they start until they finish. A stream that never ends keeps its place
+    /// for good. Waiting does not count: neither the timers of commands such as
+    /// [`crate::Command::delay`], [`crate::Command::interval`], [`crate::Command::debounce`],
+    /// [`crate::Command::throttle`] and the backoff of [`crate::Command::retry`], nor the turn of
+    /// a [`crate::Command::queued`] command. Subscriptions are not limited either.
+    pub fn max_concurrent_commands(self, value: usize) -> Self {
+        Self {
+            max_concurrent_commands: Some(value.max(1)),
+            ..self
+        }
+    }
+
+    /// Records every message handled by the host in a [`Journal`], taking a snapshot of the root
+    /// model every `snapshot_every` messages.
+    pub fn journal(self, snapshot_every: usize) -> Self
+    where
+        A::RootModel: Snapshot,
+        RootMessage<A>: Clone,
+    {
+        Self {
+            journal: Some(JournalConfig::new(snapshot_every)),
+            ..self
+        }
+    }
+
+    /// Makes the [`Journal`] forget its oldest messages once it has more than `value`, so that it
+    /// does not grow forever. Messages are forgotten a snapshot interval at a time.
+    pub fn journal_capacity(self, value: usize) -> Self {
+        Self {
+            journal_capacity: Some(value),
+            ..self
+        }
+    }
+
+    /// Persists the root model, restoring it when the host is built.
+    ///
+    /// A successfully restored model takes precedence over the one given through
+    /// [`HostBuilder::model`], which is then only used on the first launch.
+    #[cfg(feature = "serde")]
+    pub fn persist(self, value: Persist<A>) -> Self {
+        Self {
+            persist: Some(value),
+            ..self
+        }
+    }
+
+    /// Returns a handle that can stop the host once it is built and running.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.handle()
+    }
+
     pub fn buffer_size(self, value: usize) -> Self {
         Self {
             buffer_size: value,
@@ -206,19 +815,61 @@ impl<A: Application> HostBuilder<A> {
     }
 
     pub fn build(self) -> Host<A> {
-        let model = self.model.expect("RootModel was not initialized");
+        #[cfg(feature = "serde")]
+        let mut persist = self.persist;
+        let model = self.model;
+        #[cfg(feature = "serde")]
+        let model = {
+            let mut model = model;
+            if let Some(persist) = &mut persist {
+                persist.restore(&mut model);
+            }
+            model
+        };
+        let model = model.expect("RootModel was not initialized");
         let model = ModelBase::new(model);
 
         let (message_tx, message_rx) = mpsc::channel(self.buffer_size);
+        let (command_tx, command_rx) = mpsc::channel(self.buffer_size);
+        let (error_reporter, error_rx) = ErrorReporter::new();
+        let (reply_tx, reply_rx) = mpsc::unbounded();
 
         Host {
+            journal: self.journal.map(|config| {
+                let config = match self.journal_capacity {
+                    Some(capacity) => config.capacity(capacity),
+                    None => config,
+                };
+                Journal::new(config, model.clone())
+            }),
             model: model.clone(),
             world: self.world.into(),
             interceptors: self.interceptors,
+            middleware: self.middleware,
+            observers: self.observers,
+            error_handlers: self.error_handlers,
+            clone_message: self.clone_message,
+            describe_message: self.describe_message,
             spawner: self.spawner.expect("spawner was not initialized"),
+            timer: self.timer,
+            scheduler: Scheduler::new(self.max_concurrent_commands),
             signals: VecDeque::new(),
             updater: Updater::new(message_tx),
             message_rx,
+            command_updater: Updater::new(command_tx),
+            command_rx,
+            error_reporter,
+            error_rx,
+            errors: Signal::new(None),
+            reply_tx,
+            reply_rx,
+            replies: Vec::new(),
+            tasks: Tasks::default(),
+            in_flight: InFlight::default(),
+            subscriptions: HashMap::new(),
+            #[cfg(feature = "serde")]
+            persist,
+            shutdown: self.shutdown,
         }
     }
 }
@@ -229,8 +880,95 @@ impl<A: Application> Default for HostBuilder<A> {
             model: None,
             world: WorldRepr::default(),
             interceptors: Vec::new(),
+            middleware: Vec::new(),
+            observers: Vec::new(),
+            error_handlers: Vec::new(),
+            clone_message: None,
+            describe_message: None,
             spawner: None,
+            timer: HostTimer::default(),
+            max_concurrent_commands: None,
             buffer_size: DEFAULT_CHANNEL_BUFFER_SIZE,
+            journal: None,
+            journal_capacity: None,
+            #[cfg(feature = "serde")]
+            persist: None,
+            shutdown: ShutdownReceiver::new(),
         }
     }
 }
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::fixture::{TestApp, log, run, timed_host};
+    use crate::maybe::{MaybeMutex, Shared};
+    use crate::{Command, CommandError, CommandErrorKind, HostBuilder, Reply, TestHost, TimedOut};
+    use alloc::string::{String, ToString};
+    use alloc::vec::Vec;
+    use core::time::Duration;
+
+    #[test]
+    fn resolves_replies_once_the_messages_of_the_command_were_handled() {
+        let (mut host, timer) = timed_host();
+        let (reply, mut replied) = Reply::channel();
+        host.send(run(move || {
+            Command::delay(Duration::from_millis(10), log("late")).with_reply(reply.clone())
+        }));
+        assert_eq!(replied.try_recv(), Ok(None));
+        host.advance(&timer, 10);
+        assert_eq!(host.entries(), ["late"]);
+        assert_eq!(replied.try_recv(), Ok(Some(())));
+    }
+
+    #[test]
+    fn resolves_replies_of_commands_doing_nothing_after_the_update() {
+        let mut host = TestHost::<TestApp>::defaults();
+        let (reply, mut replied) = Reply::channel();
+        host.send(run(move || Command::none().with_reply(reply.clone())));
+        assert_eq!(replied.try_recv(), Ok(Some(())));
+    }
+
+    #[test]
+    fn reports_errors_to_the_handlers_with_their_origin() {
+        let handled = Shared::new(MaybeMutex::new(Vec::<String>::new()));
+        let mut host = HostBuilder::<TestApp>::defaults()
+            .describe_messages()
+            .error_handler({
+                let handled = Shared::clone(&handled);
+                move |_, error: &CommandError| handled.lock().push(error.to_string())
+            })
+            .build_test();
+        host.send(run(|| Command::done(Err(TimedOut)).report_errors()));
+        let error = host.host().errors().reader().read().clone().unwrap();
+        assert_eq!(error.origin(), Some("Run"));
+        assert!(matches!(error.kind(), CommandErrorKind::Failed(error) if error.is::<TimedOut>()));
+        assert_eq!(*handled.lock(), [error.to_string()]);
+        assert!(
+            error
+                .to_string()
+                .ends_with("returned for `Run` failed: the command timed out")
+        );
+    }
+
+    #[test]
+    fn reports_panicking_commands_and_keeps_running() {
+        let mut host = TestHost::<TestApp>::defaults();
+        host.send(run(|| Command::future(|_| async { panic!("boom") })));
+        let error = host.host().errors().reader().read().clone().unwrap();
+        assert_eq!(error.origin(), None);
+        assert!(matches!(error.kind(), CommandErrorKind::Panicked(message) if message == "boom"));
+        host.send(log("still running"));
+        assert_eq!(host.entries(), ["still running"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "called by the host")]
+    fn refuses_to_block_on_its_own_channel() {
+        let mut host = TestHost::<TestApp>::defaults();
+        let updater = host.host().updater();
+        host.send(run(move || {
+            updater.clone().send_blocking(log("never")).ok();
+            Command::none()
+        }));
+    }
+}
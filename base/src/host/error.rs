@@ -0,0 +1,133 @@
+use crate::maybe::{MaybeSendSync, Shared};
+use crate::{Application, ModelBaseReader};
+use alloc::string::String;
+use core::fmt;
+use futures::channel::mpsc;
+
+type DynError = dyn_Maybe!(SendSync core::error::Error);
+
+/// An error raised by a command instead of a message.
+#[derive(Clone, Debug)]
+pub struct CommandError {
+    message_type: &'static str,
+    origin: Option<Shared<str>>,
+    kind: CommandErrorKind,
+}
+
+#[derive(Clone, Debug)]
+pub enum CommandErrorKind {
+    /// The command produced an `Err` value, see [`crate::Command::report_errors`].
+    Failed(Shared<DynError>),
+
+    /// The stream of the command panicked with the given message. Only caught under `std`.
+    Panicked(String),
+}
+
+impl CommandError {
+    /// Creates an error raised by a command producing messages of type `T`.
+    pub fn new<T>(error: impl core::error::Error + MaybeSendSync + 'static) -> Self {
+        Self {
+            message_type: core::any::type_name::<T>(),
+            origin: None,
+            kind: CommandErrorKind::Failed(Shared::new(error)),
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn panicked<T>(payload: alloc::boxed::Box<dyn core::any::Any + Send>) -> Self {
+        use alloc::string::ToString;
+
+        let message = match payload.downcast::<String>() {
+            Ok(message) => *message,
+            Err(payload) => match payload.downcast_ref::<&str>() {
+                Some(message) => message.to_string(),
+                None => String::from("Box<dyn Any>"),
+            },
+        };
+        Self {
+            message_type: core::any::type_name::<T>(),
+            origin: None,
+            kind: CommandErrorKind::Panicked(message),
+        }
+    }
+
+    /// Returns the name of the type of messages produced by the command.
+    pub fn message_type(&self) -> &'static str {
+        self.message_type
+    }
+
+    /// Returns the message whose update returned the command, if the host was built with
+    /// [`crate::HostBuilder::describe_messages`].
+    pub fn origin(&self) -> Option<&str> {
+        self.origin.as_deref()
+    }
+
+    pub fn kind(&self) -> &CommandErrorKind {
+        &self.kind
+    }
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "a command of `{}`", self.message_type)?;
+        if let Some(origin) = &self.origin {
+            write!(f, " returned for `{origin}`")?;
+        }
+        match &self.kind {
+            CommandErrorKind::Failed(error) => write!(f, " failed: {error}"),
+            CommandErrorKind::Panicked(message) => write!(f, " panicked: {message}"),
+        }
+    }
+}
+
+impl core::error::Error for CommandError {}
+
+/// Sends the errors raised by commands to their host.
+#[derive(Clone)]
+pub struct ErrorReporter {
+    error_tx: mpsc::UnboundedSender<CommandError>,
+    // the description of the message the reporting command was returned for
+    origin: Option<Shared<str>>,
+}
+
+impl ErrorReporter {
+    pub(crate) fn new() -> (Self, mpsc::UnboundedReceiver<CommandError>) {
+        let (error_tx, error_rx) = mpsc::unbounded();
+        let reporter = Self {
+            error_tx,
+            origin: None,
+        };
+        (reporter, error_rx)
+    }
+
+    /// Returns a reporter attributing the errors it reports to the given message.
+    pub(crate) fn with_origin(&self, origin: Option<Shared<str>>) -> Self {
+        Self {
+            error_tx: self.error_tx.clone(),
+            origin,
+        }
+    }
+
+    pub fn report(&self, mut error: CommandError) {
+        if error.origin.is_none() {
+            error.origin = self.origin.clone();
+        }
+        // a host that already stopped has dropped the receiver
+        self.error_tx.unbounded_send(error).ok();
+    }
+}
+
+/// Error handlers are ran by the host for every [`CommandError`], in registration order.
+pub trait ErrorHandler<A: Application>: MaybeSendSync + 'static {
+    fn handle(&mut self, model: ModelBaseReader<A::RootModel>, error: &CommandError);
+}
+
+impl<A, F> ErrorHandler<A> for F
+where
+    A: Application,
+    F: FnMut(ModelBaseReader<A::RootModel>, &CommandError) + MaybeSendSync + 'static,
+{
+    fn handle(&mut self, model: ModelBaseReader<A::RootModel>, error: &CommandError) {
+        self(model, error)
+    }
+}
@@ -0,0 +1,249 @@
+use super::RootMessage;
+use crate::maybe::{MaybeMutex, Shared};
+use crate::{Application, FlushSignals, Model, ModelBase, Snapshot};
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+pub(crate) struct JournalConfig<A: Application> {
+    snapshot_every: usize,
+    capacity: Option<usize>,
+    clone_message: fn(&RootMessage<A>) -> RootMessage<A>,
+    snapshot: fn(&A::RootModel) -> A::RootModel,
+    restore: fn(&mut A::RootModel, &A::RootModel),
+}
+
+impl<A: Application> JournalConfig<A> {
+    pub(crate) fn new(snapshot_every: usize) -> Self
+    where
+        A::RootModel: Snapshot,
+        RootMessage<A>: Clone,
+    {
+        assert!(
+            snapshot_every > 0,
+            "`snapshot_every` must be greater than 0"
+        );
+        Self {
+            snapshot_every,
+            capacity: None,
+            clone_message: Clone::clone,
+            snapshot: Snapshot::snapshot,
+            restore: Snapshot::restore,
+        }
+    }
+
+    pub(crate) fn capacity(self, capacity: usize) -> Self {
+        Self {
+            capacity: Some(capacity),
+            ..self
+        }
+    }
+}
+
+struct JournalRepr<A: Application> {
+    config: JournalConfig<A>,
+    model: ModelBase<A::RootModel>,
+    messages: Vec<RootMessage<A>>,
+    // (amount of messages applied, snapshot of the model at that point), sorted by the former
+    snapshots: Vec<(usize, A::RootModel)>,
+    position: usize,
+}
+
+/// A log of every message handled by a host, used for time-travel debugging.
+///
+/// Created through [`crate::HostBuilder::journal`] and obtained through
+/// [`crate::Host::journal`].
+pub struct Journal<A: Application>(Shared<MaybeMutex<JournalRepr<A>>>);
+
+impl<A: Application> Journal<A> {
+    pub(crate) fn new(config: JournalConfig<A>, model: ModelBase<A::RootModel>) -> Self {
+        let initial = (config.snapshot)(&model.read());
+        Self(Shared::new(MaybeMutex::new(JournalRepr {
+            config,
+            model,
+            messages: Vec::new(),
+            snapshots: alloc::vec![(0, initial)],
+            position: 0,
+        })))
+    }
+
+    /// Records a message that is about to be handled by the host.
+    ///
+    /// If the journal was rewound, the messages after the current position are discarded first.
+    pub(crate) fn record(&self, message: &RootMessage<A>) {
+        let mut repr = self.0.lock();
+        let position = repr.position;
+        repr.messages.truncate(position);
+        repr.snapshots.retain(|(index, _)| *index <= position);
+        let message = (repr.config.clone_message)(message);
+        repr.messages.push(message);
+        repr.position += 1;
+    }
+
+    /// Takes a snapshot of the model if enough messages have been handled since the last one.
+    pub(crate) fn updated(&self) {
+        let mut repr = self.0.lock();
+        let position = repr.position;
+        if position.is_multiple_of(repr.config.snapshot_every) {
+            let snapshot = (repr.config.snapshot)(&repr.model.read());
+            repr.snapshots.push((position, snapshot));
+        }
+        if let Some(capacity) = repr.config.capacity {
+            repr.forget_before(position.saturating_sub(capacity));
+        }
+    }
+
+    /// Returns the amount of messages recorded and not yet forgotten, see
+    /// [`crate::HostBuilder::journal_capacity`].
+    pub fn len(&self) -> usize {
+        self.0.lock().messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the amount of messages that are currently applied to the model.
+    ///
+    /// This is equal to [`Journal::len`] unless the journal was rewound.
+    pub fn position(&self) -> usize {
+        self.0.lock().position
+    }
+
+    /// Returns a clone of the message at the given index, `0` being the oldest one still recorded.
+    pub fn message(&self, index: usize) -> Option<RootMessage<A>> {
+        let repr = self.0.lock();
+        repr.messages.get(index).map(repr.config.clone_message)
+    }
+
+    /// Rewinds the model to the state it had after handling the first `index` messages, then
+    /// flushes its signals.
+    ///
+    /// The model is restored from the closest snapshot and the remaining messages are replayed
+    /// through [`crate::Model::update`]. The commands they return are dropped without being run.
+    /// Rewinding forward again is possible until a new message is handled.
+    ///
+    /// # Panics
+    /// Panics if `index` is greater than [`Journal::len`].
+    pub fn rewind(&self, index: usize) {
+        let mut repr = self.0.lock();
+        assert!(
+            index <= repr.messages.len(),
+            "cannot rewind to message {index}, only {} were recorded",
+            repr.messages.len(),
+        );
+        let repr = &mut *repr;
+        let (start, snapshot) = repr
+            .snapshots
+            .iter()
+            .rev()
+            .find(|(i, _)| *i <= index)
+            .expect("the initial snapshot should always be present");
+        let mut model = repr.model.write();
+        (repr.config.restore)(&mut model, snapshot);
+        for message in &repr.messages[*start..index] {
+            let _ = model.update((repr.config.clone_message)(message));
+        }
+        drop(model);
+        repr.position = index;
+
+        let mut signals = VecDeque::<Shared<dyn FlushSignals>>::new();
+        repr.model
+            .__accumulate_signals(&mut signals, crate::__token());
+        for signal in signals {
+            signal.__flush(crate::__token());
+        }
+    }
+}
+
+impl<A: Application> JournalRepr<A> {
+    /// Forgets the oldest messages, keeping every one from `index` on.
+    ///
+    /// Messages can only be forgotten up to a snapshot, so a few more may be kept.
+    fn forget_before(&mut self, index: usize) {
+        let Some(start) = self
+            .snapshots
+            .iter()
+            .map(|(i, _)| *i)
+            .rfind(|i| *i <= index)
+            .filter(|start| *start > 0)
+        else {
+            return;
+        };
+        self.messages.drain(..start);
+        self.snapshots.retain(|(i, _)| *i >= start);
+        for (i, _) in &mut self.snapshots {
+            *i -= start;
+        }
+        self.position -= start;
+    }
+}
+
+impl<A: Application> Clone for Journal<A> {
+    fn clone(&self) -> Self {
+        Self(Shared::clone(&self.0))
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::Journal;
+    use crate::host::fixture::{LogMessage, TestApp, log, run};
+    use crate::{Command, HostBuilder, TestHost};
+    use alloc::string::String;
+
+    fn journaled(builder: HostBuilder<TestApp>) -> (TestHost<TestApp>, Journal<TestApp>) {
+        let host = builder.build_test();
+        let journal = host
+            .host()
+            .journal()
+            .expect("the host should have a journal");
+        (host, journal)
+    }
+
+    fn logged(journal: &Journal<TestApp>, index: usize) -> Option<String> {
+        match journal.message(index)? {
+            LogMessage::Log(entry) => Some(entry),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn rewinds_back_and_forth_until_a_new_message_is_handled() {
+        let (mut host, journal) = journaled(HostBuilder::defaults().journal(2));
+        for entry in ["a", "b", "c"] {
+            host.send(log(entry));
+        }
+        journal.rewind(1);
+        assert_eq!(host.entries(), ["a"]);
+        assert_eq!((journal.position(), journal.len()), (1, 3));
+        journal.rewind(3);
+        assert_eq!(host.entries(), ["a", "b", "c"]);
+        journal.rewind(1);
+        host.send(log("d"));
+        assert_eq!(host.entries(), ["a", "d"]);
+        assert_eq!((journal.position(), journal.len()), (2, 2));
+        assert_eq!(logged(&journal, 1).as_deref(), Some("d"));
+    }
+
+    #[test]
+    fn drops_the_commands_of_replayed_messages() {
+        let (mut host, journal) = journaled(HostBuilder::defaults().journal(1));
+        host.send(run(|| Command::done(log("from command"))));
+        assert_eq!(journal.len(), 2);
+        journal.rewind(1);
+        host.run_until_stalled();
+        assert!(host.entries().is_empty());
+    }
+
+    #[test]
+    fn forgets_the_oldest_messages_a_snapshot_interval_at_a_time() {
+        let (mut host, journal) = journaled(HostBuilder::defaults().journal(2).journal_capacity(2));
+        for entry in ["a", "b", "c", "d", "e"] {
+            host.send(log(entry));
+        }
+        assert_eq!((journal.position(), journal.len()), (3, 3));
+        assert_eq!(logged(&journal, 0).as_deref(), Some("c"));
+        journal.rewind(0);
+        assert_eq!(host.entries(), ["a", "b"]);
+    }
+}
@@ -0,0 +1,436 @@
+use super::HostTimer;
+use crate::maybe::{
+    MaybeLocalBoxFuture, MaybeMutex, MaybeSend, MaybeSendSync, Shared, boxed_future,
+};
+use crate::{Application, ModelBase, Snapshot};
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum PersistError {
+    #[cfg(feature = "std")]
+    #[error("failed to access the storage: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("failed to (de)serialize the model: {0}")]
+    Serde(#[from] serde_json::Error),
+
+    #[error(
+        "the stored model has version {stored}, which is newer than the current version {current}"
+    )]
+    NewerVersion { stored: u32, current: u32 },
+
+    #[error("there is no migration from version {0}")]
+    MissingMigration(u32),
+}
+
+/// Where persisted models are stored.
+pub trait Storage: MaybeSend + 'static {
+    /// Loads the previously saved data, if any.
+    fn load(&mut self) -> Result<Option<Vec<u8>>, PersistError>;
+
+    /// Saves the given data, replacing what was saved before.
+    ///
+    /// Called from the task saving the model. Under a tokio runtime with the `thread-safe` feature,
+    /// it runs on the blocking thread pool. Otherwise, it runs on the spawner of the host, so a
+    /// storage that blocks while saving also blocks whatever else runs on that spawner.
+    fn save(&mut self, data: &[u8]) -> Result<(), PersistError>;
+}
+
+/// A [`Storage`] that saves the data to a single file.
+///
+/// Reading and writing the file block the calling thread, see [`Storage::save`] for where that
+/// happens.
+#[cfg(feature = "std")]
+pub struct FileStorage {
+    path: std::path::PathBuf,
+}
+
+#[cfg(feature = "std")]
+impl FileStorage {
+    pub fn new(path: impl Into<std::path::PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Storage for FileStorage {
+    fn load(&mut self) -> Result<Option<Vec<u8>>, PersistError> {
+        match std::fs::read(&self.path) {
+            Ok(data) => Ok(Some(data)),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    fn save(&mut self, data: &[u8]) -> Result<(), PersistError> {
+        // write to a sibling file first so that a crash mid-write never corrupts the last save
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        std::fs::write(&tmp, data)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+type Migration = Box<dyn_Maybe!(Send Fn(Value) -> Value)>;
+type SerializeFn<A> = Shared<
+    dyn_Maybe!(SendSync Fn(&<A as Application>::RootModel) -> Result<Value, serde_json::Error>),
+>;
+type RestoreFn<A> = Box<
+    dyn_Maybe!(Send Fn(
+        Value,
+        &mut Option<<A as Application>::RootModel>,
+    ) -> Result<(), serde_json::Error>),
+>;
+
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    version: u32,
+    model: Value,
+}
+
+/// Saves the root model, or parts of it, to a [`Storage`] and restores it when the host is built.
+///
+/// Saving happens on a task spawned by the host once it runs out of queued messages after handling
+/// at least one, so a burst of messages results in a single save. [`Persist::debounce`] further
+/// spaces out the saves.
+pub struct Persist<A: Application> {
+    storage: Shared<MaybeMutex<Box<dyn Storage>>>,
+    version: u32,
+    migrations: BTreeMap<u32, Migration>,
+    serialize: SerializeFn<A>,
+    restore: RestoreFn<A>,
+    debounce: Option<Duration>,
+    dirty: Shared<AtomicBool>,
+    pending: Shared<AtomicBool>,
+}
+
+impl<A: Application> Persist<A> {
+    /// Persists the whole root model.
+    ///
+    /// On restore, the saved model is deserialized apart, then written into the model given
+    /// through [`crate::HostBuilder::model`] with [`Snapshot::restore`], so that its signals keep
+    /// their comparators and the signals derived from them. Without a model given, the
+    /// deserialized one is used as is, with signals that were all created anew.
+    pub fn new(storage: impl Storage) -> Self
+    where
+        A::RootModel: serde::Serialize + DeserializeOwned + Snapshot,
+    {
+        Self::with(
+            storage,
+            Shared::new(|model: &A::RootModel| serde_json::to_value(model)),
+            Box::new(|value, model| {
+                let restored = serde_json::from_value(value)?;
+                match model {
+                    Some(model) => model.restore(&restored),
+                    None => *model = Some(restored),
+                }
+                Ok(())
+            }),
+        )
+    }
+
+    /// Persists only the value returned by `select`, such as a tuple of some of the fields of the
+    /// root model.
+    ///
+    /// On restore, `apply` writes the saved value into the model given through
+    /// [`crate::HostBuilder::model`], which is required.
+    pub fn selected<S>(
+        storage: impl Storage,
+        select: impl Fn(&A::RootModel) -> S + MaybeSendSync + 'static,
+        apply: impl Fn(&mut A::RootModel, S) + MaybeSend + 'static,
+    ) -> Self
+    where
+        S: serde::Serialize + DeserializeOwned,
+    {
+        Self::with(
+            storage,
+            Shared::new(move |model: &A::RootModel| serde_json::to_value(select(model))),
+            Box::new(move |value, model| {
+                let selected = serde_json::from_value(value)?;
+                match model {
+                    Some(model) => apply(model, selected),
+                    None => tracing::warn!("there is no model to restore the selected value into"),
+                }
+                Ok(())
+            }),
+        )
+    }
+
+    fn with(storage: impl Storage, serialize: SerializeFn<A>, restore: RestoreFn<A>) -> Self {
+        Self {
+            storage: Shared::new(MaybeMutex::new(Box::new(storage))),
+            version: 0,
+            migrations: BTreeMap::new(),
+            serialize,
+            restore,
+            debounce: None,
+            dirty: Shared::new(AtomicBool::new(false)),
+            pending: Shared::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Sets the version of the current shape of the model. Defaults to `0`.
+    pub fn version(self, value: u32) -> Self {
+        Self {
+            version: value,
+            ..self
+        }
+    }
+
+    /// Registers a migration that converts a model saved with version `from` into version
+    /// `from + 1`.
+    pub fn migration(
+        mut self,
+        from: u32,
+        f: impl Fn(Value) -> Value + MaybeSend + 'static,
+    ) -> Self {
+        self.migrations.insert(from, Box::new(f));
+        self
+    }
+
+    /// Waits for `value` after the first change before saving, as measured by the
+    /// [`crate::Timer`] of the host, so that the changes made in the meantime are saved together.
+    ///
+    /// A host without a timer saves right away.
+    pub fn debounce(self, value: Duration) -> Self {
+        Self {
+            debounce: Some(value),
+            ..self
+        }
+    }
+
+    /// Restores the model over the one set on the host builder, which is kept if that fails.
+    pub(crate) fn restore(&mut self, model: &mut Option<A::RootModel>) {
+        let result = self.load().and_then(|data| match data {
+            Some(data) => (self.restore)(data, model).map_err(PersistError::from),
+            None => Ok(()),
+        });
+        if let Err(error) = result {
+            tracing::warn!(%error, "failed to restore the persisted model");
+        }
+    }
+
+    fn load(&mut self) -> Result<Option<Value>, PersistError> {
+        let Some(data) = self.storage.lock().load()? else {
+            return Ok(None);
+        };
+        let Envelope { version, mut model } = serde_json::from_slice(&data)?;
+        if version > self.version {
+            return Err(PersistError::NewerVersion {
+                stored: version,
+                current: self.version,
+            });
+        }
+        for from in version..self.version {
+            let migration = self
+                .migrations
+                .get(&from)
+                .ok_or(PersistError::MissingMigration(from))?;
+            model = migration(model);
+        }
+        Ok(Some(model))
+    }
+
+    pub(crate) fn mark_dirty(&self) {
+        self.dirty.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns the task saving the model if it changed and no save is pending already.
+    pub(crate) fn save_task(
+        &self,
+        model: &ModelBase<A::RootModel>,
+        timer: &HostTimer,
+    ) -> Option<MaybeLocalBoxFuture<'static, ()>> {
+        if !self.dirty.load(Ordering::Relaxed) || self.pending.swap(true, Ordering::Relaxed) {
+            return None;
+        }
+        let sleep = self.debounce.and_then(|duration| match timer.get() {
+            Some(timer) => Some(timer.sleep(duration)),
+            None => {
+                tracing::warn!("the host has no timer to debounce the saves with");
+                None
+            }
+        });
+        let save = self.save_fn(model);
+        let pending = Shared::clone(&self.pending);
+        Some(boxed_future(async move {
+            if let Some(sleep) = sleep {
+                sleep.await;
+            }
+            pending.store(false, Ordering::Relaxed);
+
+            // the storage may block, which must not stall the other tasks of the runtime
+            #[cfg(all(feature = "thread-safe", feature = "tokio"))]
+            if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+                if let Err(error) = runtime.spawn_blocking(save).await {
+                    tracing::error!(%error, "the task saving the model failed");
+                }
+                return;
+            }
+
+            save();
+        }))
+    }
+
+    /// Saves the model right away if it changed, blocking the host while the storage is written.
+    pub(crate) fn flush(&self, model: &ModelBase<A::RootModel>) {
+        self.save_fn(model)();
+    }
+
+    fn save_fn(&self, model: &ModelBase<A::RootModel>) -> impl FnOnce() + MaybeSend + 'static {
+        let model = model.reader();
+        let storage = Shared::clone(&self.storage);
+        let serialize = Shared::clone(&self.serialize);
+        let dirty = Shared::clone(&self.dirty);
+        let version = self.version;
+        move || {
+            if !dirty.swap(false, Ordering::Relaxed) {
+                return;
+            }
+            let result = serialize(&model.read())
+                .and_then(|model| serde_json::to_vec(&Envelope { version, model }))
+                .map_err(PersistError::from)
+                .and_then(|data| storage.lock().save(&data));
+            if let Err(error) = result {
+                tracing::error!(%error, "failed to persist the model");
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::{Persist, PersistError, Storage};
+    use crate::host::fixture::{GetCount, GetItems, LogModel, TestApp, log};
+    use crate::maybe::{MaybeMutex, Shared};
+    use crate::{HostBuilder, ManualTimer};
+    use alloc::string::String;
+    use alloc::vec::Vec;
+    use core::time::Duration;
+    use serde_json::{Value, json};
+
+    #[derive(Clone, Default)]
+    struct MemoryStorage(Shared<MaybeMutex<Option<Vec<u8>>>>);
+
+    impl MemoryStorage {
+        fn with(value: Value) -> Self {
+            let storage = Self::default();
+            *storage.0.lock() = Some(serde_json::to_vec(&value).unwrap());
+            storage
+        }
+
+        fn saved(&self) -> Option<Value> {
+            let data = self.0.lock().clone()?;
+            Some(serde_json::from_slice(&data).unwrap())
+        }
+    }
+
+    impl Storage for MemoryStorage {
+        fn load(&mut self) -> Result<Option<Vec<u8>>, PersistError> {
+            Ok(self.0.lock().clone())
+        }
+
+        fn save(&mut self, data: &[u8]) -> Result<(), PersistError> {
+            *self.0.lock() = Some(data.into());
+            Ok(())
+        }
+    }
+
+    /// Persists the entries of the model.
+    fn entries(storage: &MemoryStorage) -> Persist<TestApp> {
+        Persist::selected(
+            storage.clone(),
+            |model: &LogModel| model.entries.reader().read().clone(),
+            |model: &mut LogModel, entries: Vec<String>| model.entries.writer().set(entries),
+        )
+    }
+
+    #[test]
+    fn restores_the_selected_value_into_the_model() {
+        let storage = MemoryStorage::with(json!({ "version": 0, "model": ["restored"] }));
+        let host = HostBuilder::defaults()
+            .persist(entries(&storage))
+            .build_test();
+        assert_eq!(host.entries(), ["restored"]);
+    }
+
+    #[test]
+    fn restores_the_whole_model_into_the_given_one() {
+        let storage = MemoryStorage::with(json!({ "version": 0, "model": [["a", "b"], ["x"]] }));
+        let mut host = HostBuilder::defaults()
+            .persist(Persist::new(storage))
+            .build_test();
+        assert_eq!(host.entries(), ["a", "b"]);
+        assert_eq!(*host.get::<GetItems>().reader().read(), ["x"]);
+        // the count is still derived from the entries of the model the host was given
+        host.send(log("c"));
+        assert_eq!(*host.get::<GetCount>().reader().read(), 3);
+    }
+
+    #[test]
+    fn migrates_older_versions_in_order() {
+        let storage = MemoryStorage::with(json!({ "version": 0, "model": ["v0"] }));
+        let migrate = |version: &'static str| {
+            move |mut model: Value| {
+                model.as_array_mut().unwrap().push(version.into());
+                model
+            }
+        };
+        let persist = entries(&storage)
+            .version(2)
+            .migration(1, migrate("v2"))
+            .migration(0, migrate("v1"));
+        let host = HostBuilder::defaults().persist(persist).build_test();
+        assert_eq!(host.entries(), ["v0", "v1", "v2"]);
+    }
+
+    #[test]
+    fn keeps_the_given_model_when_the_stored_one_is_newer() {
+        let storage = MemoryStorage::with(json!({ "version": 1, "model": ["newer"] }));
+        let host = HostBuilder::defaults()
+            .persist(entries(&storage))
+            .build_test();
+        assert!(host.entries().is_empty());
+    }
+
+    #[test]
+    fn saves_once_the_host_is_idle() {
+        let storage = MemoryStorage::default();
+        let mut host = HostBuilder::defaults()
+            .persist(entries(&storage).version(3))
+            .build_test();
+        host.send(log("saved"));
+        assert_eq!(
+            storage.saved(),
+            Some(json!({ "version": 3, "model": ["saved"] }))
+        );
+    }
+
+    #[test]
+    fn debounces_the_saves_with_the_timer_of_the_host() {
+        let storage = MemoryStorage::default();
+        let timer = ManualTimer::new();
+        let mut host = HostBuilder::defaults()
+            .timer(timer.clone())
+            .persist(entries(&storage).debounce(Duration::from_millis(10)))
+            .build_test();
+        host.send(log("first"));
+        host.advance(&timer, 5);
+        host.send(log("second"));
+        assert_eq!(storage.saved(), None);
+        host.advance(&timer, 5);
+        assert_eq!(
+            storage.saved().unwrap()["model"],
+            json!(["first", "second"])
+        );
+    }
+}
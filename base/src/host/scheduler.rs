@@ -0,0 +1,260 @@
+use crate::CommandId;
+use crate::maybe::{MaybeLocalBoxFuture, MaybeMutex, Shared, boxed_future};
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use core::pin::Pin;
+use core::task::{Context, Poll, ready};
+use futures::channel::oneshot;
+use futures::{FutureExt, Stream, StreamExt};
+use hashbrown::HashMap;
+
+/// What a command waits for before it runs.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum Slot {
+    /// A place among the commands the host runs at once, see
+    /// [`crate::HostBuilder::max_concurrent_commands`].
+    Global,
+    /// The turn of the command among those sharing its key, see [`crate::Command::queued`].
+    Keyed(CommandId),
+}
+
+#[derive(Default)]
+struct Permits {
+    running: usize,
+    waiters: VecDeque<oneshot::Sender<()>>,
+}
+
+#[derive(Default)]
+struct SchedulerRepr {
+    limit: Option<usize>,
+    global: Permits,
+    keyed: HashMap<CommandId, Permits>,
+}
+
+/// Holds back the commands of a host until they are allowed to run, in the order they arrived.
+#[derive(Clone, Default)]
+pub(crate) struct Scheduler(Shared<MaybeMutex<SchedulerRepr>>);
+
+impl Scheduler {
+    pub(crate) fn new(limit: Option<usize>) -> Self {
+        Self(Shared::new(MaybeMutex::new(SchedulerRepr {
+            limit,
+            ..SchedulerRepr::default()
+        })))
+    }
+
+    /// Builds a stream with `make` once it gets the slot, which it then keeps until the stream is
+    /// dropped, so that nothing the stream starts when built runs before its turn.
+    pub(crate) fn schedule<S, F>(&self, slot: Slot, make: F) -> Scheduled<S, F>
+    where
+        S: Stream,
+        F: FnOnce() -> S,
+    {
+        let acquiring =
+            (!self.is_unlimited(&slot)).then(|| boxed_future(self.clone().acquire(slot)));
+        Scheduled {
+            acquiring,
+            permit: None,
+            make: Some(make),
+            stream: None,
+        }
+    }
+
+    /// Delays the given future until it gets the slot, which it then keeps until it finishes.
+    pub(crate) async fn run<F: Future>(self, slot: Slot, fut: F) -> F::Output {
+        let _permit = if self.is_unlimited(&slot) {
+            None
+        } else {
+            Some(self.acquire(slot).await)
+        };
+        fut.await
+    }
+
+    fn is_unlimited(&self, slot: &Slot) -> bool {
+        *slot == Slot::Global && self.0.lock().limit.is_none()
+    }
+
+    async fn acquire(self, slot: Slot) -> Permit {
+        let granted = {
+            let mut repr = self.0.lock();
+            let limit = match &slot {
+                Slot::Global => repr.limit.unwrap_or(usize::MAX),
+                Slot::Keyed(_) => 1,
+            };
+            let permits = repr.permits(&slot);
+            if permits.running < limit {
+                permits.running += 1;
+                None
+            } else {
+                let (grant_tx, grant_rx) = oneshot::channel();
+                permits.waiters.push_back(grant_tx);
+                Some(grant_rx)
+            }
+        };
+        if let Some(granted) = granted {
+            let mut waiting = Waiting {
+                scheduler: self.clone(),
+                slot: slot.clone(),
+                granted: Some(granted),
+            };
+            // the sender lives in the scheduler we hold, so it is never dropped before granting
+            if let Some(granted) = &mut waiting.granted {
+                granted.await.ok();
+            }
+            waiting.granted = None;
+        }
+        Permit {
+            scheduler: self,
+            slot,
+        }
+    }
+
+    fn release(&self, slot: &Slot) {
+        let mut repr = self.0.lock();
+        let permits = repr.permits(slot);
+        // hand the permit over to the first waiter that is still around
+        while let Some(waiter) = permits.waiters.pop_front() {
+            if waiter.send(()).is_ok() {
+                return;
+            }
+        }
+        permits.running -= 1;
+        if let Slot::Keyed(id) = slot
+            && permits.running == 0
+        {
+            repr.keyed.remove(id);
+        }
+    }
+}
+
+impl SchedulerRepr {
+    fn permits(&mut self, slot: &Slot) -> &mut Permits {
+        match slot {
+            Slot::Global => &mut self.global,
+            Slot::Keyed(id) => self.keyed.entry(id.clone()).or_default(),
+        }
+    }
+}
+
+/// A stream that is only built once it got its slot, see [`Scheduler::schedule`].
+pub(crate) struct Scheduled<S, F> {
+    acquiring: Option<MaybeLocalBoxFuture<'static, Permit>>,
+    permit: Option<Permit>,
+    make: Option<F>,
+    stream: Option<Pin<Box<S>>>,
+}
+
+// the closure is only ever moved out, never pinned, and the stream is pinned on the heap
+impl<S, F> Unpin for Scheduled<S, F> {}
+
+impl<S: Stream, F: FnOnce() -> S> Stream for Scheduled<S, F> {
+    type Item = S::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<S::Item>> {
+        if let Some(acquiring) = &mut self.acquiring {
+            let permit = ready!(acquiring.poll_unpin(cx));
+            self.acquiring = None;
+            self.permit = Some(permit);
+        }
+        if let Some(make) = self.make.take() {
+            self.stream = Some(Box::pin(make()));
+        }
+        match &mut self.stream {
+            Some(stream) => stream.poll_next_unpin(cx),
+            None => Poll::Ready(None),
+        }
+    }
+}
+
+struct Permit {
+    scheduler: Scheduler,
+    slot: Slot,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.scheduler.release(&self.slot);
+    }
+}
+
+// Releases the permit if it was granted but its waiter dropped before getting it.
+struct Waiting {
+    scheduler: Scheduler,
+    slot: Slot,
+    granted: Option<oneshot::Receiver<()>>,
+}
+
+impl Drop for Waiting {
+    fn drop(&mut self) {
+        if let Some(granted) = &mut self.granted
+            && let Ok(Some(())) = granted.try_recv()
+        {
+            self.scheduler.release(&self.slot);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Scheduler, Slot};
+    use core::cell::Cell;
+    use core::task::{Context, Poll};
+    use futures::task::noop_waker_ref;
+    use futures::{Stream, StreamExt, stream};
+
+    fn poll<S: Stream + Unpin>(stream: &mut S) -> Poll<Option<S::Item>> {
+        stream.poll_next_unpin(&mut Context::from_waker(noop_waker_ref()))
+    }
+
+    #[test]
+    fn builds_streams_once_they_got_their_slot_in_order() {
+        let scheduler = Scheduler::new(Some(1));
+        let built = Cell::new(0);
+        let mut first = scheduler.schedule(Slot::Global, || {
+            built.set(built.get() + 1);
+            stream::iter([1])
+        });
+        let mut second = scheduler.schedule(Slot::Global, || {
+            built.set(built.get() + 1);
+            stream::iter([2])
+        });
+        assert_eq!(poll(&mut first), Poll::Ready(Some(1)));
+        assert!(poll(&mut second).is_pending());
+        assert_eq!(built.get(), 1);
+        assert_eq!(poll(&mut first), Poll::Ready(None));
+        drop(first);
+        assert_eq!(poll(&mut second), Poll::Ready(Some(2)));
+        assert_eq!(built.get(), 2);
+    }
+
+    #[test]
+    fn hands_the_slot_over_to_the_next_waiter_still_around() {
+        let scheduler = Scheduler::new(Some(1));
+        let mut running = scheduler.schedule(Slot::Global, stream::pending::<u32>);
+        let mut dropped = scheduler.schedule(Slot::Global, || stream::iter([1]));
+        let mut waiting = scheduler.schedule(Slot::Global, || stream::iter([2]));
+        assert!(poll(&mut running).is_pending());
+        assert!(poll(&mut dropped).is_pending());
+        assert!(poll(&mut waiting).is_pending());
+        drop(dropped);
+        drop(running);
+        assert_eq!(poll(&mut waiting), Poll::Ready(Some(2)));
+    }
+
+    #[test]
+    fn does_not_limit_other_keys_or_an_unlimited_host() {
+        let scheduler = Scheduler::new(None);
+        let mut running = scheduler.schedule(Slot::Global, stream::pending::<u32>);
+        let mut unlimited = scheduler.schedule(Slot::Global, || stream::iter([1]));
+        assert!(poll(&mut running).is_pending());
+        assert_eq!(poll(&mut unlimited), Poll::Ready(Some(1)));
+
+        let keyed = |key| Slot::Keyed(crate::CommandId::new(key));
+        let mut first = scheduler.schedule(keyed("first"), stream::pending::<u32>);
+        let mut second = scheduler.schedule(keyed("second"), || stream::iter([2]));
+        let mut queued = scheduler.schedule(keyed("first"), || stream::iter([3]));
+        assert!(poll(&mut first).is_pending());
+        assert_eq!(poll(&mut second), Poll::Ready(Some(2)));
+        assert!(poll(&mut queued).is_pending());
+    }
+}
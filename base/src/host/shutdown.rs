@@ -0,0 +1,198 @@
+use crate::maybe::{MaybeLocalBoxFuture, MaybeMutex, MaybeSend, Shared, boxed_future};
+use alloc::vec::Vec;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use core::task::{Context, Poll, Waker};
+use futures::channel::mpsc;
+use futures::stream::{AbortHandle, Abortable};
+use futures::task::AtomicWaker;
+use futures::{Stream, StreamExt};
+use hashbrown::HashMap;
+
+pub(crate) enum ShutdownRequest {
+    Immediate,
+    Drain {
+        deadline: MaybeLocalBoxFuture<'static, ()>,
+    },
+}
+
+#[derive(Default)]
+struct StoppedRepr {
+    stopped: AtomicBool,
+    wakers: MaybeMutex<Vec<Waker>>,
+}
+
+/// Asks a host to stop, and tells when it has.
+///
+/// Obtained through [`crate::HostBuilder::shutdown_handle`] or
+/// [`crate::Host::shutdown_handle`].
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    request_tx: mpsc::UnboundedSender<ShutdownRequest>,
+    stopped: Shared<StoppedRepr>,
+}
+
+impl ShutdownHandle {
+    /// Stops the host once it is done with the message being handled, dropping the queued messages
+    /// and aborting the commands in flight.
+    pub fn shutdown(&self) -> Stopped {
+        self.request(ShutdownRequest::Immediate)
+    }
+
+    /// Stops accepting new messages, then keeps handling the queued ones, along with the messages
+    /// produced by the commands in flight, until the host is idle or `deadline` resolves.
+    ///
+    /// Pass [`futures::future::pending`] to wait for as long as it takes.
+    pub fn drain(&self, deadline: impl Future<Output = ()> + MaybeSend + 'static) -> Stopped {
+        self.request(ShutdownRequest::Drain {
+            deadline: boxed_future(deadline),
+        })
+    }
+
+    fn request(&self, request: ShutdownRequest) -> Stopped {
+        // a host that already stopped has dropped the receiver
+        self.request_tx.unbounded_send(request).ok();
+        self.stopped()
+    }
+
+    /// Returns a future that resolves once the host has fully stopped.
+    pub fn stopped(&self) -> Stopped {
+        Stopped(Shared::clone(&self.stopped))
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.stopped.load(Ordering::Acquire)
+    }
+}
+
+/// Resolves once a host has fully stopped: its signals were destroyed and its commands aborted.
+pub struct Stopped(Shared<StoppedRepr>);
+
+impl Future for Stopped {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.0.stopped.load(Ordering::Acquire) {
+            return Poll::Ready(());
+        }
+        let mut wakers = self.0.wakers.lock();
+        // checked again under the lock, as the host may have stopped in the meantime
+        if self.0.stopped.load(Ordering::Acquire) {
+            return Poll::Ready(());
+        }
+        if !wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+            wakers.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
+
+/// The side of the shutdown handle owned by the host. Marks the host as stopped when dropped.
+pub(crate) struct ShutdownReceiver {
+    request_rx: mpsc::UnboundedReceiver<ShutdownRequest>,
+    handle: ShutdownHandle,
+}
+
+impl ShutdownReceiver {
+    pub(crate) fn new() -> Self {
+        let (request_tx, request_rx) = mpsc::unbounded();
+        Self {
+            request_rx,
+            handle: ShutdownHandle {
+                request_tx,
+                stopped: Shared::default(),
+            },
+        }
+    }
+
+    pub(crate) fn handle(&self) -> ShutdownHandle {
+        self.handle.clone()
+    }
+
+    pub(crate) fn try_recv(&mut self) -> Option<ShutdownRequest> {
+        self.request_rx.try_recv().ok()
+    }
+
+    pub(crate) async fn recv(&mut self) -> Option<ShutdownRequest> {
+        self.request_rx.next().await
+    }
+}
+
+impl Drop for ShutdownReceiver {
+    fn drop(&mut self) {
+        let stopped = &self.handle.stopped;
+        stopped.stopped.store(true, Ordering::Release);
+        for waker in core::mem::take(&mut *stopped.wakers.lock()) {
+            waker.wake();
+        }
+    }
+}
+
+#[derive(Default)]
+struct InFlightRepr {
+    next_task: AtomicU64,
+    live: MaybeMutex<HashMap<u64, AbortHandle>>,
+    idle_waker: AtomicWaker,
+}
+
+/// Tracks the commands spawned by a host, so that it can wait for or abort them on shutdown.
+#[derive(Clone, Default)]
+pub(crate) struct InFlight(Shared<InFlightRepr>);
+
+impl InFlight {
+    pub(crate) fn track<S>(&self, stream: S) -> Tracked<S> {
+        let (handle, registration) = AbortHandle::new_pair();
+        let task = self.0.next_task.fetch_add(1, Ordering::Relaxed);
+        self.0.live.lock().insert(task, handle);
+        Tracked {
+            stream: Abortable::new(stream, registration),
+            in_flight: self.clone(),
+            task,
+        }
+    }
+
+    pub(crate) fn abort_all(&self) {
+        for (_, handle) in self.0.live.lock().drain() {
+            handle.abort();
+        }
+    }
+
+    pub(crate) fn is_idle(&self) -> bool {
+        self.0.live.lock().is_empty()
+    }
+
+    /// Resolves once no command is in flight.
+    pub(crate) async fn idle(&self) {
+        futures::future::poll_fn(|cx| {
+            self.0.idle_waker.register(cx.waker());
+            if self.is_idle() {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await
+    }
+}
+
+pub(crate) struct Tracked<S> {
+    stream: Abortable<S>,
+    in_flight: InFlight,
+    task: u64,
+}
+
+impl<S: Stream + Unpin> Stream for Tracked<S> {
+    type Item = S::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.stream).poll_next(cx)
+    }
+}
+
+impl<S> Drop for Tracked<S> {
+    fn drop(&mut self) {
+        let repr = &self.in_flight.0;
+        repr.live.lock().remove(&self.task);
+        repr.idle_waker.wake();
+    }
+}
@@ -31,13 +31,51 @@ mod global {
     }
 }
 
+#[cfg(not(feature = "thread-safe"))]
+mod local {
+    #[cfg(feature = "std")]
+    mod pool {
+        use crate::host::spawner::Spawner;
+        use crate::maybe::MaybeLocalBoxFuture;
+        use futures::executor::LocalSpawner;
+        use futures::task::LocalSpawnExt;
+
+        impl Spawner for LocalSpawner {
+            fn spawn_detached_dyn(&mut self, fut: MaybeLocalBoxFuture<'static, ()>) {
+                if let Err(error) = self.spawn_local(fut) {
+                    tracing::warn!(%error, "failed to spawn a future on the local pool");
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    pub mod tokio {
+        use crate::host::spawner::Spawner;
+        use crate::maybe::MaybeLocalBoxFuture;
+
+        /// Spawns futures on the current [`tokio::task::LocalSet`].
+        #[derive(Clone, Default)]
+        pub struct GlobalTokioLocalSpawner;
+
+        impl Spawner for GlobalTokioLocalSpawner {
+            fn spawn_detached_dyn(&mut self, fut: MaybeLocalBoxFuture<'static, ()>) {
+                tokio::task::spawn_local(fut);
+            }
+        }
+    }
+}
+
 #[cfg(all(feature = "thread-safe", feature = "tokio"))]
 pub use global::tokio::GlobalTokioSpawner;
 
+#[cfg(all(not(feature = "thread-safe"), feature = "tokio"))]
+pub use local::tokio::GlobalTokioLocalSpawner;
+
 #[cfg(all(feature = "thread-safe", feature = "frb-compat"))]
 pub use global::frb::GlobalFrbSpawner;
 
-use crate::maybe::{boxed_future, MaybeLocalBoxFuture, MaybeSend};
+use crate::maybe::{MaybeLocalBoxFuture, MaybeSend, boxed_future};
 
 pub trait Spawner: MaybeSend {
     fn spawn_detached_dyn(&mut self, fut: MaybeLocalBoxFuture<'static, ()>);
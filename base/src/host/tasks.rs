@@ -0,0 +1,212 @@
+use crate::maybe::{MaybeLocalBoxStream, MaybeMutex, MaybeSendSync, Shared, boxed_stream};
+use alloc::vec::Vec;
+use core::any::{Any, TypeId};
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::pin::Pin;
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::task::{Context, Poll};
+use futures::Stream;
+use futures::stream::{AbortHandle, Abortable};
+use hashbrown::HashMap;
+
+/// Identifies a group of live command tasks.
+///
+/// Any `Hash + Eq + 'static` value can be used as a key; keys of different types never collide.
+#[derive(Clone)]
+pub struct CommandId {
+    hash: u64,
+    key: Shared<dyn Key>,
+}
+
+impl CommandId {
+    pub fn new<K: Hash + Eq + MaybeSendSync + 'static>(key: K) -> Self {
+        let mut hasher = KeyHasher::default();
+        TypeId::of::<K>().hash(&mut hasher);
+        key.hash(&mut hasher);
+        Self {
+            hash: hasher.finish(),
+            key: Shared::new(key),
+        }
+    }
+}
+
+impl PartialEq for CommandId {
+    fn eq(&self, other: &Self) -> bool {
+        // the keys are only compared when the hashes match, which they nearly always do then
+        self.hash == other.hash && self.key.eq_dyn(&*other.key)
+    }
+}
+
+impl Eq for CommandId {}
+
+impl Hash for CommandId {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.hash);
+    }
+}
+
+impl fmt::Debug for CommandId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CommandId")
+            .field("hash", &self.hash)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A key of any type, compared with keys of other types through [`Any`].
+trait Key: Any + MaybeSendSync {
+    fn eq_dyn(&self, other: &dyn Key) -> bool;
+
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<K: Eq + MaybeSendSync + 'static> Key for K {
+    fn eq_dyn(&self, other: &dyn Key) -> bool {
+        other.as_any().downcast_ref::<K>() == Some(self)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// FNV-1a, chosen because it is deterministic across calls and needs no `std`.
+struct KeyHasher(u64);
+
+impl Default for KeyHasher {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for KeyHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+#[derive(Default)]
+struct TasksRepr {
+    next_task: AtomicU64,
+    live: MaybeMutex<HashMap<CommandId, Vec<(u64, AbortHandle)>>>,
+}
+
+/// Tracks the live command tasks of a host by their [`CommandId`].
+#[derive(Clone, Default)]
+pub struct Tasks(Shared<TasksRepr>);
+
+impl Tasks {
+    /// Aborts every live task registered under the given key, dropping their streams.
+    ///
+    /// Returns the amount of tasks that were cancelled.
+    pub fn cancel<K: Hash + Eq + MaybeSendSync + 'static>(&self, key: K) -> usize {
+        self.cancel_id(&CommandId::new(key))
+    }
+
+    pub fn cancel_id(&self, id: &CommandId) -> usize {
+        let handles = self.0.live.lock().remove(id).unwrap_or_default();
+        for (_, handle) in &handles {
+            handle.abort();
+        }
+        handles.len()
+    }
+
+    pub fn is_running<K: Hash + Eq + MaybeSendSync + 'static>(&self, key: K) -> bool {
+        self.is_running_id(&CommandId::new(key))
+    }
+
+    pub fn is_running_id(&self, id: &CommandId) -> bool {
+        self.0.live.lock().contains_key(id)
+    }
+
+    pub(crate) fn register<T: 'static>(
+        &self,
+        id: CommandId,
+        stream: MaybeLocalBoxStream<'static, T>,
+    ) -> MaybeLocalBoxStream<'static, T> {
+        let (handle, registration) = AbortHandle::new_pair();
+        let task = self.0.next_task.fetch_add(1, Ordering::Relaxed);
+        self.0
+            .live
+            .lock()
+            .entry(id.clone())
+            .or_default()
+            .push((task, handle));
+        boxed_stream(Registered {
+            stream: Abortable::new(stream, registration),
+            _guard: RegistrationGuard {
+                tasks: self.clone(),
+                id,
+                task,
+            },
+        })
+    }
+
+    fn unregister(&self, id: &CommandId, task: u64) {
+        let mut live = self.0.live.lock();
+        if let Some(handles) = live.get_mut(id) {
+            handles.retain(|(t, _)| *t != task);
+            if handles.is_empty() {
+                live.remove(id);
+            }
+        }
+    }
+}
+
+struct RegistrationGuard {
+    tasks: Tasks,
+    id: CommandId,
+    task: u64,
+}
+
+impl Drop for RegistrationGuard {
+    fn drop(&mut self) {
+        self.tasks.unregister(&self.id, self.task);
+    }
+}
+
+struct Registered<S> {
+    stream: Abortable<S>,
+    _guard: RegistrationGuard,
+}
+
+impl<S: Stream + Unpin> Stream for Registered<S> {
+    type Item = S::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.stream).poll_next(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CommandId;
+    use core::hash::{Hash, Hasher};
+
+    /// A key whose values all hash the same.
+    #[derive(PartialEq, Eq)]
+    struct Colliding(u32);
+
+    impl Hash for Colliding {
+        fn hash<H: Hasher>(&self, _state: &mut H) {}
+    }
+
+    #[test]
+    fn tells_keys_with_the_same_hash_apart() {
+        assert_eq!(CommandId::new(Colliding(1)), CommandId::new(Colliding(1)));
+        assert_ne!(CommandId::new(Colliding(1)), CommandId::new(Colliding(2)));
+    }
+
+    #[test]
+    fn tells_keys_of_different_types_apart() {
+        assert_ne!(CommandId::new(1u32), CommandId::new(1u64));
+    }
+}
@@ -0,0 +1,467 @@
+use super::{Host, HostBuilder, RootMessage};
+use crate::maybe::{MaybeLocalBoxFuture, MaybeMutex, MaybeRwLockReadGuard, Shared};
+use crate::{Application, ModelGetterHandler, ModelGetterMessage, Signal, SignalId, Spawner};
+use alloc::vec::Vec;
+use core::any::type_name;
+use core::mem;
+use futures::executor::LocalPool;
+use futures::task::LocalSpawnExt;
+
+/// A [`Spawner`] that queues futures until a [`TestHost`] runs them.
+#[derive(Clone, Default)]
+pub struct TestSpawner(Shared<MaybeMutex<Vec<MaybeLocalBoxFuture<'static, ()>>>>);
+
+impl TestSpawner {
+    fn take(&self) -> Vec<MaybeLocalBoxFuture<'static, ()>> {
+        mem::take(&mut *self.0.lock())
+    }
+}
+
+impl Spawner for TestSpawner {
+    fn spawn_detached_dyn(&mut self, fut: MaybeLocalBoxFuture<'static, ()>) {
+        self.0.lock().push(fut);
+    }
+}
+
+/// Something that happened inside a [`TestHost`].
+pub enum TestEvent<M> {
+    /// A message was sent to the host through [`TestHost::send`].
+    Message(M),
+
+    /// A message was produced by a command or a subscription.
+    CommandOutput(M),
+
+    /// The signals were flushed after an update. Contains the amount of signals that changed,
+    /// including the derived ones.
+    Flushed(usize),
+}
+
+/// A host that runs commands deterministically on the current thread.
+///
+/// Nothing runs in the background: commands and subscriptions only make progress while
+/// [`TestHost::send`] or [`TestHost::run_until_stalled`] is being called.
+pub struct TestHost<A: Application> {
+    host: Host<A>,
+    spawner: TestSpawner,
+    pool: LocalPool,
+    events: Vec<TestEvent<RootMessage<A>>>,
+    changed: Vec<SignalId>,
+}
+
+impl<A: Application> TestHost<A>
+where
+    RootMessage<A>: Clone,
+{
+    pub fn new(model: A::RootModel) -> Self {
+        HostBuilder::new().model(model).build_test()
+    }
+
+    pub fn defaults() -> Self
+    where
+        A::RootModel: Default,
+    {
+        HostBuilder::defaults().build_test()
+    }
+
+    /// Handles the given message and runs the resulting commands until they stall.
+    ///
+    /// The signals reported by [`TestHost::signal_changed`] are reset before the message is
+    /// handled.
+    pub fn send(&mut self, message: RootMessage<A>) {
+        self.changed.clear();
+        self.events.push(TestEvent::Message(message.clone()));
+        self.handle(message);
+        self.run_until_stalled();
+    }
+
+    /// Runs the spawned commands and handles the messages they produce until no more progress
+    /// can be made.
+    pub fn run_until_stalled(&mut self) {
+        loop {
+            self.spawn_queued();
+            self.pool.run_until_stalled();
+
+            // error handlers may send messages, so they run before the queue is drained
+            self.host.handle_errors();
+            self.host.collect_replies();
+            let mut handled = false;
+            while let Some(message) = self.host.try_next_message() {
+                self.events.push(TestEvent::CommandOutput(message.clone()));
+                self.handle(message);
+                handled = true;
+            }
+
+            // resolving a reply wakes up whoever waits for it
+            if !handled && self.host.resolve_replies() {
+                continue;
+            }
+            if !handled && self.spawner.0.lock().is_empty() {
+                // idling may spawn a task, such as saving the model
+                self.host.on_idle();
+                if self.spawner.0.lock().is_empty() {
+                    break;
+                }
+            }
+        }
+    }
+
+    fn spawn_queued(&mut self) {
+        let spawner = self.pool.spawner();
+        for fut in self.spawner.take() {
+            spawner
+                .spawn_local(fut)
+                .expect("the local pool should not be shut down");
+        }
+    }
+
+    fn handle(&mut self, message: RootMessage<A>) {
+        let mut changed = 0;
+        let fut = self.host.handle_message_with(message, |signal| {
+            changed += 1;
+            self.changed.push(signal);
+        });
+        self.pool.run_until(fut);
+        self.events.push(TestEvent::Flushed(changed));
+    }
+}
+
+impl<A: Application> TestHost<A> {
+    pub fn host(&self) -> &Host<A> {
+        &self.host
+    }
+
+    pub fn read(&self) -> MaybeRwLockReadGuard<'_, A::RootModel> {
+        self.host.model.read()
+    }
+
+    pub fn get<Msg>(&self) -> Signal<Msg::Data>
+    where
+        Msg: ModelGetterMessage,
+        A::RootModel: ModelGetterHandler<Msg>,
+    {
+        self.host.model.get()
+    }
+
+    /// Returns every event recorded so far, in order.
+    pub fn events(&self) -> &[TestEvent<RootMessage<A>>] {
+        &self.events
+    }
+
+    /// Removes and returns every event recorded so far.
+    pub fn take_events(&mut self) -> Vec<TestEvent<RootMessage<A>>> {
+        mem::take(&mut self.events)
+    }
+
+    /// Returns every message handled by the host so far, whether sent by the test or produced by
+    /// a command.
+    pub fn messages(&self) -> impl Iterator<Item = &RootMessage<A>> {
+        self.events.iter().filter_map(|event| match event {
+            TestEvent::Message(message) | TestEvent::CommandOutput(message) => Some(message),
+            TestEvent::Flushed(_) => None,
+        })
+    }
+
+    /// Returns every message produced by a command or a subscription so far.
+    pub fn command_outputs(&self) -> impl Iterator<Item = &RootMessage<A>> {
+        self.events.iter().filter_map(|event| match event {
+            TestEvent::CommandOutput(message) => Some(message),
+            _ => None,
+        })
+    }
+
+    /// Returns `true` if the signal of the given getter was flushed as changed since the last
+    /// call to [`TestHost::send`], whether it is a plain signal, a collection, or a derived signal
+    /// whose sources changed.
+    pub fn signal_changed<Msg>(&self) -> bool
+    where
+        Msg: ModelGetterMessage,
+        A::RootModel: ModelGetterHandler<Msg>,
+    {
+        self.changed.contains(&self.get::<Msg>().id())
+    }
+
+    #[track_caller]
+    pub fn assert_signal_changed<Msg>(&self)
+    where
+        Msg: ModelGetterMessage,
+        A::RootModel: ModelGetterHandler<Msg>,
+    {
+        assert!(
+            self.signal_changed::<Msg>(),
+            "expected the signal of `{}` to have changed",
+            type_name::<Msg>()
+        );
+    }
+
+    #[track_caller]
+    pub fn assert_signal_unchanged<Msg>(&self)
+    where
+        Msg: ModelGetterMessage,
+        A::RootModel: ModelGetterHandler<Msg>,
+    {
+        assert!(
+            !self.signal_changed::<Msg>(),
+            "expected the signal of `{}` to be unchanged",
+            type_name::<Msg>()
+        );
+    }
+}
+
+impl<A: Application> HostBuilder<A> {
+    /// Builds a [`TestHost`], replacing any spawner with a [`TestSpawner`].
+    pub fn build_test(self) -> TestHost<A> {
+        let spawner = TestSpawner::default();
+        let mut host = self.spawner(spawner.clone()).build();
+        host.update_subscriptions();
+        TestHost {
+            host,
+            spawner,
+            pool: LocalPool::new(),
+            events: Vec::new(),
+            changed: Vec::new(),
+        }
+    }
+}
+
+/// A model that logs entries and runs the commands carried by its messages, for the tests of the
+/// host and its commands.
+#[cfg(test)]
+pub(crate) mod fixture {
+    use super::TestHost;
+    use crate::maybe::{MaybeSendSync, Shared};
+    use crate::{__private, Application, Command, Model, Signal, Snapshot, Subscription};
+    use crate::{__token, FlushSignals, GetterField, HostBuilder, ManualTimer};
+    use crate::{ModelGetterHandler, ModelGetterMessage, SignalVec};
+    use alloc::collections::VecDeque;
+    use alloc::format;
+    use alloc::string::{String, ToString};
+    use alloc::vec::Vec;
+    use core::fmt;
+    use futures::{StreamExt, future, stream};
+
+    pub(crate) struct TestApp;
+
+    impl Application for TestApp {
+        type RootModel = LogModel;
+    }
+
+    type MakeCommand = Shared<dyn_Maybe!(SendSync Fn() -> Command<LogMessage, TestApp>)>;
+
+    #[derive(Clone)]
+    pub(crate) enum LogMessage {
+        Log(String),
+        Push(String),
+        Run(MakeCommand),
+        Subscribe(Vec<u32>),
+    }
+
+    impl fmt::Debug for LogMessage {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Self::Log(entry) => f.debug_tuple("Log").field(entry).finish(),
+                Self::Push(item) => f.debug_tuple("Push").field(item).finish(),
+                Self::Run(_) => f.write_str("Run"),
+                Self::Subscribe(keys) => f.debug_tuple("Subscribe").field(keys).finish(),
+            }
+        }
+    }
+
+    /// Returns a message logging the given entry.
+    pub(crate) fn log(entry: impl ToString) -> LogMessage {
+        LogMessage::Log(entry.to_string())
+    }
+
+    /// Returns a message whose update returns the command built by `f`.
+    pub(crate) fn run(
+        f: impl Fn() -> Command<LogMessage, TestApp> + MaybeSendSync + 'static,
+    ) -> LogMessage {
+        LogMessage::Run(Shared::new(f))
+    }
+
+    /// Logs the entries it receives, and counts them in a derived signal. Every key it is
+    /// subscribed to logs `start <key>` when its subscription starts.
+    pub(crate) struct LogModel {
+        pub(crate) entries: Signal<Vec<String>>,
+        count: Signal<usize>,
+        items: SignalVec<String>,
+        subscribed: Vec<u32>,
+    }
+
+    impl LogModel {
+        fn with(entries: Signal<Vec<String>>, items: SignalVec<String>) -> Self {
+            Self {
+                count: entries.map(Vec::len),
+                entries,
+                items,
+                subscribed: Vec::new(),
+            }
+        }
+    }
+
+    impl Default for LogModel {
+        fn default() -> Self {
+            Self::with(Signal::default(), SignalVec::default())
+        }
+    }
+
+    /// Persisted as the pair of its entries and items.
+    #[cfg(feature = "serde")]
+    impl serde::Serialize for LogModel {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            (&self.entries, &self.items).serialize(serializer)
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    impl<'de> serde::Deserialize<'de> for LogModel {
+        fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let (entries, items) = serde::Deserialize::deserialize(deserializer)?;
+            Ok(Self::with(entries, items))
+        }
+    }
+
+    pub(crate) struct GetCount;
+
+    impl ModelGetterMessage for GetCount {
+        type Data = usize;
+    }
+
+    impl ModelGetterHandler<GetCount> for LogModel {
+        fn getter(&self) -> Signal<usize> {
+            self.count.clone()
+        }
+    }
+
+    pub(crate) struct GetItems;
+
+    impl ModelGetterMessage for GetItems {
+        type Data = Vec<String>;
+    }
+
+    impl ModelGetterHandler<GetItems> for LogModel {
+        fn getter(&self) -> Signal<Vec<String>> {
+            self.items.__signal(__token())
+        }
+    }
+
+    impl Model for LogModel {
+        type ForApp = TestApp;
+        type Message = LogMessage;
+
+        fn update(&mut self, message: LogMessage) -> Command<LogMessage, TestApp> {
+            match message {
+                LogMessage::Log(entry) => self.entries.writer().update(|e| e.push(entry)),
+                LogMessage::Push(item) => self.items.writer().push(item),
+                LogMessage::Run(command) => return command(),
+                LogMessage::Subscribe(keys) => self.subscribed = keys,
+            }
+            Command::none()
+        }
+
+        fn subscriptions(&self) -> Subscription<LogMessage, TestApp> {
+            Subscription::batch(self.subscribed.iter().map(|&key| {
+                Subscription::run(key, move |_| {
+                    let started = log(format!("start {key}"));
+                    stream::once(future::ready(started)).chain(stream::pending())
+                })
+            }))
+        }
+
+        fn __accumulate_signals(
+            &self,
+            signals: &mut VecDeque<Shared<dyn FlushSignals>>,
+            _token: __private::Token,
+        ) {
+            signals.push_back(self.entries.__to_dyn_flush_signals(__token()));
+            signals.push_back(self.items.__to_dyn_flush_signals(__token()));
+        }
+    }
+
+    impl Snapshot for LogModel {
+        fn snapshot(&self) -> Self {
+            Self {
+                subscribed: self.subscribed.clone(),
+                ..Self::with(self.entries.snapshot(), self.items.snapshot())
+            }
+        }
+
+        fn restore(&mut self, snapshot: &Self) {
+            self.entries.restore(&snapshot.entries);
+            self.items.restore(&snapshot.items);
+            self.subscribed.clone_from(&snapshot.subscribed);
+        }
+    }
+
+    /// Returns a test host whose clock only moves through the returned timer.
+    pub(crate) fn timed_host() -> (TestHost<TestApp>, ManualTimer) {
+        let timer = ManualTimer::new();
+        let host = HostBuilder::defaults().timer(timer.clone()).build_test();
+        (host, timer)
+    }
+
+    impl TestHost<TestApp> {
+        /// Moves the clock of the host forward, then runs it until it stalls.
+        pub(crate) fn advance(&mut self, timer: &ManualTimer, millis: u64) {
+            timer.advance(core::time::Duration::from_millis(millis));
+            self.run_until_stalled();
+        }
+
+        pub(crate) fn entries(&self) -> Vec<String> {
+            self.read().entries.reader().read().clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TestEvent;
+    use super::fixture::{GetCount, GetItems, LogMessage, TestApp, log, run};
+    use crate::{Command, TestHost};
+    use alloc::string::String;
+    use alloc::vec::Vec;
+
+    fn describe(event: &TestEvent<LogMessage>) -> String {
+        match event {
+            TestEvent::Message(message) => alloc::format!("message {message:?}"),
+            TestEvent::CommandOutput(message) => alloc::format!("output {message:?}"),
+            TestEvent::Flushed(changed) => alloc::format!("flushed {changed}"),
+        }
+    }
+
+    #[test]
+    fn records_messages_command_outputs_and_flushes_in_order() {
+        let mut host = TestHost::<TestApp>::defaults();
+        host.send(run(|| Command::done(log("from command"))));
+        let events: Vec<_> = host.take_events().iter().map(describe).collect();
+        assert_eq!(
+            events,
+            [
+                "message Run",
+                "flushed 0",
+                "output Log(\"from command\")",
+                // the entries and the count derived from them
+                "flushed 2",
+            ]
+        );
+        assert!(host.events().is_empty());
+    }
+
+    #[test]
+    fn tells_which_collection_getters_changed() {
+        let mut host = TestHost::<TestApp>::defaults();
+        host.send(LogMessage::Push("item".into()));
+        host.assert_signal_changed::<GetItems>();
+        host.send(log("entry"));
+        host.assert_signal_unchanged::<GetItems>();
+    }
+
+    #[test]
+    fn tells_which_derived_getters_changed() {
+        let mut host = TestHost::<TestApp>::defaults();
+        host.send(log("entry"));
+        host.assert_signal_changed::<GetCount>();
+        assert_eq!(*host.get::<GetCount>().reader().read(), 1);
+        host.send(LogMessage::Push("item".into()));
+        host.assert_signal_unchanged::<GetCount>();
+    }
+}
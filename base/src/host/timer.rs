@@ -0,0 +1,112 @@
+use crate::maybe::{MaybeLocalBoxFuture, MaybeMutex, MaybeSendSync, Shared, boxed_future};
+use alloc::vec::Vec;
+use core::mem;
+use core::time::Duration;
+use futures::channel::oneshot;
+use thiserror::Error;
+
+/// Measures time for the commands of a host, see [`crate::Command::delay`].
+pub trait Timer: MaybeSendSync + 'static {
+    fn sleep(&self, duration: Duration) -> MaybeLocalBoxFuture<'static, ()>;
+}
+
+/// The error reported by the commands that need to wait when their host was built without a
+/// [`Timer`].
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error("the host was built without a timer")]
+pub struct MissingTimer;
+
+#[cfg(feature = "tokio")]
+mod tokio {
+    use super::Timer;
+    use crate::maybe::{MaybeLocalBoxFuture, boxed_future};
+    use core::time::Duration;
+
+    /// A [`Timer`] backed by `tokio::time`. Must be used from within a tokio runtime.
+    #[derive(Clone, Copy, Debug, Default)]
+    pub struct TokioTimer;
+
+    impl Timer for TokioTimer {
+        fn sleep(&self, duration: Duration) -> MaybeLocalBoxFuture<'static, ()> {
+            boxed_future(tokio::time::sleep(duration))
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+pub use self::tokio::TokioTimer;
+
+#[derive(Default)]
+struct ManualRepr {
+    now: Duration,
+    sleepers: Vec<(Duration, oneshot::Sender<()>)>,
+}
+
+/// A [`Timer`] whose time only passes when told to, for deterministic tests.
+///
+/// Register a clone of it on the [`crate::HostBuilder`], then call [`ManualTimer::advance`] and
+/// let the host run, e.g. through [`crate::TestHost::run_until_stalled`].
+#[derive(Clone, Default)]
+pub struct ManualTimer(Shared<MaybeMutex<ManualRepr>>);
+
+impl ManualTimer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the time elapsed since the timer was created.
+    pub fn now(&self) -> Duration {
+        self.0.lock().now
+    }
+
+    /// Moves the clock forward, waking the sleeps that are due.
+    pub fn advance(&self, duration: Duration) {
+        let due = {
+            let mut repr = self.0.lock();
+            repr.now += duration;
+            let now = repr.now;
+            let (due, pending) = mem::take(&mut repr.sleepers)
+                .into_iter()
+                .partition::<Vec<_>, _>(|(deadline, _)| *deadline <= now);
+            repr.sleepers = pending;
+            due
+        };
+        for (_, sleeper) in due {
+            sleeper.send(()).ok();
+        }
+    }
+}
+
+impl Timer for ManualTimer {
+    fn sleep(&self, duration: Duration) -> MaybeLocalBoxFuture<'static, ()> {
+        let (wake_tx, wake_rx) = oneshot::channel();
+        if duration.is_zero() {
+            wake_tx.send(()).ok();
+        } else {
+            let mut repr = self.0.lock();
+            repr.sleepers.retain(|(_, sleeper)| !sleeper.is_canceled());
+            let deadline = repr.now + duration;
+            repr.sleepers.push((deadline, wake_tx));
+        }
+        boxed_future(async move {
+            if wake_rx.await.is_err() {
+                // the timer is gone, so time stopped
+                futures::future::pending::<()>().await
+            }
+        })
+    }
+}
+
+/// The timer of a host, shared with its commands.
+#[derive(Clone, Default)]
+pub(crate) struct HostTimer(Option<Shared<dyn Timer>>);
+
+impl HostTimer {
+    pub(crate) fn new(timer: impl Timer) -> Self {
+        Self(Some(Shared::new(timer)))
+    }
+
+    pub(crate) fn get(&self) -> Option<&Shared<dyn Timer>> {
+        self.0.as_ref()
+    }
+}
@@ -23,7 +23,7 @@ pub mod __macros {
     #[cfg(feature = "frb-compat")]
     pub use flutter_rust_bridge::frb;
 
-    pub use crate::FlushSignals;
+    pub use crate::{FlushSignals, GetterField};
     pub use crate::maybe::Shared;
 }
 
@@ -51,18 +51,28 @@ pub mod dispatcher;
 
 pub mod command;
 pub mod host;
+pub mod region;
+pub mod subscription;
 
 #[cfg(feature = "thread-safe")]
 pub mod handle;
 
+#[cfg(not(feature = "thread-safe"))]
+pub mod local_handle;
+
 pub use base::*;
 pub use command::*;
 pub use dispatcher::*;
 pub use host::*;
+pub use region::*;
+pub use subscription::*;
 
 #[cfg(feature = "thread-safe")]
 pub use handle::*;
 
+#[cfg(not(feature = "thread-safe"))]
+pub use local_handle::*;
+
 #[doc(hidden)]
 pub fn __token() -> __private::Token {
     __private::Token::new()
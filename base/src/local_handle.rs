@@ -0,0 +1,49 @@
+use crate::{Application, Host, HostBuilder, Spawner, WrappedGetter, WrappedUpdater};
+
+#[cfg(feature = "tokio")]
+use crate::{GlobalTokioLocalSpawner, TokioTimer};
+
+app_handle! {
+    /// The app handle of builds without the `thread-safe` feature, whose host runs on a
+    /// single-threaded executor such as a [`futures::executor::LocalPool`] or a
+    /// `tokio::task::LocalSet`.
+    pub struct LocalAppHandle;
+}
+
+impl<A, WU, WG> LocalAppHandle<A, WU, WG>
+where
+    A: Application,
+    WU: WrappedUpdater<Model = A::RootModel>,
+    WG: WrappedGetter<Model = A::RootModel>,
+{
+    /// Builds the host and runs it on `S`, which is also the default spawner for its commands.
+    pub fn new<S: Spawner + Default + 'static>(
+        builder_fn: impl FnOnce(HostBuilder<A>) -> Host<A>,
+    ) -> Self {
+        let host = builder_fn(HostBuilder::new().spawner(S::default()));
+        Self::run(host, S::default())
+    }
+
+    /// Builds the host and runs it on the given spawner, which is also the default spawner for
+    /// its commands.
+    ///
+    /// Useful for spawners that cannot be created out of thin air, like the
+    /// [`futures::executor::LocalSpawner`] of a specific pool.
+    pub fn with_spawner<S: Spawner + Clone + 'static>(
+        spawner: S,
+        builder_fn: impl FnOnce(HostBuilder<A>) -> Host<A>,
+    ) -> Self {
+        let host = builder_fn(HostBuilder::new().spawner(spawner.clone()));
+        Self::run(host, spawner)
+    }
+
+    /// Runs the host on the current `tokio::task::LocalSet`, with a [`TokioTimer`] for its
+    /// commands.
+    #[cfg(feature = "tokio")]
+    pub fn new_tokio_local(builder_fn: impl FnOnce(HostBuilder<A>) -> Host<A>) -> Self {
+        let builder = HostBuilder::new()
+            .spawner(GlobalTokioLocalSpawner)
+            .timer(TokioTimer);
+        Self::run(builder_fn(builder), GlobalTokioLocalSpawner)
+    }
+}
@@ -1,6 +1,38 @@
+/// Declares an opaque flutter_rust_bridge type wrapping an [`crate::AppHandle`], with functions
+/// exposing it to Dart.
+///
+/// `SplittableWrappedDispatcher` names [`crate::Dispatcher`] of the wrapped updater and getter,
+/// usually through a type alias, which `dispatcher` returns.
 #[cfg(feature = "frb-compat")]
 #[macro_export]
 macro_rules! wrap_app_handle_for_frb {
+    (@optional errors [$(#[$($meta:meta)*])*] $vis:vis $StreamSink:ident) => {
+        $(#[$($meta)*])*
+        $vis async fn errors(&self, sink: $StreamSink<$crate::__macros::alloc::string::String>) -> $crate::__macros::anyhow::Result<()> {
+            let mut errors = self.0.errors().watch();
+            $crate::__macros::flutter_rust_bridge::spawn(async move {
+                while let Some(error) = $crate::__macros::futures::StreamExt::next(&mut errors).await {
+                    if let Some(error) = error {
+                        sink.add($crate::__macros::alloc::string::ToString::to_string(&error)).ok();
+                    }
+                }
+            });
+            ::core::result::Result::Ok(())
+        }
+    };
+    (@optional closed [$(#[$($meta:meta)*])*] $vis:vis $StreamSink:ident) => {
+        $(#[$($meta)*])*
+        $vis async fn closed(&self) {
+            self.0.closed().await
+        }
+    };
+    (@optional $other:ident [$($meta:tt)*] $vis:vis $StreamSink:ident) => {
+        ::core::compile_error!(::core::concat!(
+            "unknown app handle function `",
+            ::core::stringify!($other),
+            "`, expected `errors` or `closed`"
+        ));
+    };
     (
         $(#[$($meta:meta)*])*
         $vis:vis struct $AppHandleWrapper:ident
@@ -28,17 +60,22 @@ macro_rules! wrap_app_handle_for_frb {
 
             $(#[$($should_refresh_meta:meta)*])*
             $should_refresh_vis:vis fn should_refresh;
+
+            $(
+                $(#[$($optional_meta:meta)*])*
+                $optional_vis:vis fn $optional:ident;
+            )*
         }
     ) => {
         #[$crate::__macros::frb(opaque)]
         $(#[$($meta)*])*
-        $vis struct $AppHandleWrapper($crate::AppHandle<$Application, $SplittableWrappedDispatcher>);
+        $vis struct $AppHandleWrapper($crate::AppHandle<$Application, $WrappedUpdater, $WrappedGetter>);
 
         impl $AppHandleWrapper {
             $(#[$($new_meta)*])*
             $new_vis async fn new($($bfn_arg: $bfn_arg_ty),*) -> Self {
                 let builder_fn = |$builder: $crate::HostBuilder<$Application>, $($bfn_arg: $bfn_arg_ty),*| $builder_fn;
-                Self($crate::handle::AppHandle::new::<$crate::handle::FrbSpawner>(|$builder| builder_fn($builder, $($bfn_arg),*)))
+                Self($crate::AppHandle::new_frb(|$builder| builder_fn($builder, $($bfn_arg),*)))
             }
 
             #[$crate::__macros::frb(sync, getter)]
@@ -59,10 +96,9 @@ macro_rules! wrap_app_handle_for_frb {
                 self.0.getter()
             }
 
-            #[$crate::__macros::frb(sync)]
             $(#[$($should_refresh_meta)*])*
-            $should_refresh_vis async fn should_refresh(&mut self, sink: $StreamSink<$RegionId>) -> $crate::__macros::anyhow::Result<()> {
-                let mut subscriber = self.0.should_refresh();
+            $should_refresh_vis async fn should_refresh(&self, sink: $StreamSink<$RegionId>) -> $crate::__macros::anyhow::Result<()> {
+                let mut subscriber = self.0.should_refresh::<$RegionId>();
                 $crate::__macros::flutter_rust_bridge::spawn(async move {
                     while let Some(region) = $crate::__macros::futures::StreamExt::next(&mut subscriber).await {
                         sink.add(region).ok();
@@ -70,33 +106,25 @@ macro_rules! wrap_app_handle_for_frb {
                 });
                 ::core::result::Result::Ok(())
             }
+
+            $(
+                $crate::wrap_app_handle_for_frb! {
+                    @optional $optional [$(#[$($optional_meta)*])*] $optional_vis $StreamSink
+                }
+            )*
         }
     };
 }
 
+/// Joins the given signals into a signal of a tuple of their values, like [`crate::Signal::join`].
 #[macro_export]
 macro_rules! join_signals {
-    ($Spawner:ty, $($signal:ident),+ $(,)?) => {{
-        use $crate::__macros::futures::{select, FutureExt};
-        $(let $signal = $signal;)+
-        let initial = ($($signal.reader().read().clone()),+);
-        let output = $crate::Signal::new(initial);
-        let writer = output.writer();
-        $(let mut $signal = $signal.subscribe();)+
-        <$Spawner as $crate::GlobalSpawner>::spawn_detached(async move {
-            loop {
-                select! {
-                    $(_ = $signal.recv_status().fuse() => {}),+
-                    complete => break,
-                }
-                let new_values = ($($signal.read().clone()),+);
-                writer.set(new_values);
-            }
-        });
-        output
-    }};
+    ($($signal:expr),+ $(,)?) => {
+        $crate::Signal::join(($(&$signal,)+))
+    };
 }
 
+#[allow(unused_macros)]
 macro_rules! maybe_async_trait {
     ($($item:item)*) => {
         $(
@@ -106,3 +134,182 @@ macro_rules! maybe_async_trait {
         )*
     };
 }
+
+/// Declares an app handle, along with what `AppHandle` and `LocalAppHandle` share:
+/// everything but the constructors, which run the host on their own kind of spawner through the
+/// generated `run`.
+macro_rules! app_handle {
+    ($(#[$meta:meta])* $vis:vis struct $AppHandle:ident;) => {
+        $(#[$meta])*
+        $vis struct $AppHandle<A: $crate::Application, WU, WG> {
+            updater: WU,
+            getter: WG,
+            raw_getter: $crate::Getter<A::RootModel>,
+            errors: $crate::Signal<Option<$crate::CommandError>>,
+            shutdown: $crate::ShutdownHandle,
+            _app: ::core::marker::PhantomData<A>,
+        }
+
+        impl<A, WU, WG> $AppHandle<A, WU, WG>
+        where
+            A: $crate::Application,
+            WU: $crate::WrappedUpdater<Model = A::RootModel>,
+            WG: $crate::WrappedGetter<Model = A::RootModel>,
+        {
+            fn run(host: $crate::Host<A>, mut spawner: impl $crate::Spawner) -> Self {
+                let updater = host.updater();
+                let getter = host.getter();
+                let errors = host.errors();
+                let shutdown = host.shutdown_handle();
+                $crate::SpawnerExt::spawn_detached(&mut spawner, host.run());
+                Self {
+                    updater: WU::__new(updater, $crate::__token()),
+                    getter: WG::__new(getter.clone(), $crate::__token()),
+                    raw_getter: getter,
+                    errors,
+                    shutdown,
+                    _app: ::core::marker::PhantomData,
+                }
+            }
+
+            pub fn updater(&self) -> WU {
+                self.updater.clone()
+            }
+
+            pub fn getter(&self) -> WG {
+                self.getter.clone()
+            }
+
+            pub fn dispatcher(&self) -> $crate::Dispatcher<WU, WG> {
+                $crate::Dispatcher::new(self.updater(), self.getter())
+            }
+
+            /// Returns a signal of the last error raised by a command, see
+            /// [`crate::ErrorHandler`].
+            pub fn errors(&self) -> $crate::Signal<Option<$crate::CommandError>> {
+                self.errors.clone()
+            }
+
+            pub fn shutdown_handle(&self) -> $crate::ShutdownHandle {
+                self.shutdown.clone()
+            }
+
+            /// Returns a future that resolves once the host has stopped, be it through its
+            /// [`crate::ShutdownHandle`] or because it panicked. From then on, messages sent
+            /// through the updater are rejected with [`crate::Error::HostChannelClosed`].
+            pub fn closed(&self) -> $crate::Stopped {
+                self.shutdown.stopped()
+            }
+
+            pub fn is_closed(&self) -> bool {
+                self.shutdown.is_stopped()
+            }
+
+            /// Returns a stream that yields the id of a region whenever one of its signals
+            /// changes.
+            pub fn should_refresh<R>(&self) -> $crate::maybe::MaybeLocalBoxStream<'static, R>
+            where
+                R: $crate::RegionId<Model = A::RootModel>,
+            {
+                R::regions(&self.raw_getter).into_stream()
+            }
+        }
+    };
+}
+
+#[cfg(all(test, feature = "frb-compat", feature = "std"))]
+mod tests {
+    use crate::__private;
+    use crate::host::fixture::{GetCount, LogModel, TestApp};
+    use crate::{Dispatcher, Getter, RegionId, Regions, Updater, WrappedGetter, WrappedUpdater};
+    use core::marker::PhantomData;
+
+    #[derive(Clone)]
+    struct LogUpdater;
+
+    impl WrappedUpdater for LogUpdater {
+        type Model = LogModel;
+
+        fn __new(_updater: Updater<LogModel>, _token: __private::Token) -> Self {
+            Self
+        }
+    }
+
+    impl __private::Sealed for LogUpdater {}
+
+    #[derive(Clone)]
+    struct LogGetter;
+
+    impl WrappedGetter for LogGetter {
+        type Model = LogModel;
+
+        fn __new(_getter: Getter<LogModel>, _token: __private::Token) -> Self {
+            Self
+        }
+    }
+
+    impl __private::Sealed for LogGetter {}
+
+    #[derive(Clone)]
+    struct LogRegion;
+
+    impl RegionId for LogRegion {
+        type Model = LogModel;
+
+        fn regions(getter: &Getter<LogModel>) -> Regions<Self> {
+            Regions::new().signal(Self, &getter.get::<GetCount>())
+        }
+    }
+
+    /// Stands for the sink generated by flutter_rust_bridge.
+    struct StreamSink<T>(PhantomData<T>);
+
+    impl<T> StreamSink<T> {
+        fn add(&self, _value: T) -> Result<(), ()> {
+            Ok(())
+        }
+    }
+
+    type LogDispatcher = Dispatcher<LogUpdater, LogGetter>;
+
+    wrap_app_handle_for_frb! {
+        struct LogAppHandle
+        where
+            Application = TestApp,
+            SplittableWrappedDispatcher = LogDispatcher,
+            WrappedUpdater = LogUpdater,
+            WrappedGetter = LogGetter,
+            StreamSink = StreamSink,
+            RegionId = LogRegion,
+        {
+            let builder_fn = |builder, buffer_size: usize| {
+                builder.default_model().buffer_size(buffer_size).build()
+            }
+
+            pub fn new;
+            pub fn dispatcher;
+            pub fn updater;
+            pub fn getter;
+            pub fn should_refresh;
+            pub fn errors;
+            pub fn closed;
+        }
+    }
+
+    #[test]
+    fn wraps_an_app_handle() {
+        // never polled, since the host would need the runtime of flutter_rust_bridge. This only
+        // checks that the expansion compiles with the expected signatures.
+        let _wrapped = async {
+            let handle = LogAppHandle::new(16).await;
+            let dispatcher: LogDispatcher = handle.dispatcher();
+            let _ = (handle.updater(), handle.getter(), dispatcher.split());
+            handle
+                .should_refresh(StreamSink(PhantomData))
+                .await
+                .unwrap();
+            handle.errors(StreamSink(PhantomData)).await.unwrap();
+            handle.closed().await;
+        };
+    }
+}
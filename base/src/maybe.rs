@@ -8,6 +8,7 @@ mod impls {
     impl<T: Sync> MaybeSync for T {}
     impl<T: 'static> MaybeStatic for T {}
     pub type Shared<T> = alloc::sync::Arc<T>;
+    pub type MaybeWeak<T> = alloc::sync::Weak<T>;
     pub type MaybeLocalBoxFuture<'a, T> = futures::future::BoxFuture<'a, T>;
     pub type MaybeLocalBoxStream<'a, T> = futures::stream::BoxStream<'a, T>;
 }
@@ -22,13 +23,14 @@ mod impls {
     impl<T> MaybeSync for T {}
     impl<T> MaybeStatic for T {}
     pub type Shared<T> = alloc::rc::Rc<T>;
+    pub type MaybeWeak<T> = alloc::rc::Weak<T>;
     pub type MaybeLocalBoxFuture<'a, T> = futures::future::LocalBoxFuture<'a, T>;
     pub type MaybeLocalBoxStream<'a, T> = futures::stream::LocalBoxStream<'a, T>;
 }
 
 use futures::{FutureExt, StreamExt};
 pub use impls::{
-    MaybeLocalBoxFuture, MaybeLocalBoxStream, MaybeSend, MaybeStatic, MaybeSync, Shared,
+    MaybeLocalBoxFuture, MaybeLocalBoxStream, MaybeSend, MaybeStatic, MaybeSync, MaybeWeak, Shared,
 };
 
 #[cfg(feature = "thread-safe")]
@@ -61,27 +63,119 @@ mod sync {
     }
 
     // --- MaybeRwLock ---
+    #[derive(Default)]
     pub struct MaybeRwLock<T>(RwLockImpl<T>);
-    pub struct MaybeRwLockReadGuard<'a, T>(RwLockReadGuardImpl<'a, T>);
-    pub struct MaybeRwLockWriteGuard<'a, T>(RwLockWriteGuardImpl<'a, T>);
+    pub struct MaybeRwLockReadGuard<'a, T> {
+        guard: RwLockReadGuardImpl<'a, T>,
+        _held: Held,
+    }
+    pub struct MaybeRwLockWriteGuard<'a, T> {
+        guard: RwLockWriteGuardImpl<'a, T>,
+        _held: Held,
+    }
 
     impl<T> MaybeRwLock<T> {
         pub fn new(value: T) -> Self {
             Self(RwLockImpl::new(value))
         }
         pub fn read(&self) -> MaybeRwLockReadGuard<'_, T> {
-            MaybeRwLockReadGuard(unwrap_lock!(self.0.read()))
+            MaybeRwLockReadGuard {
+                guard: unwrap_lock!(self.0.read()),
+                _held: Held::new(self),
+            }
         }
         pub fn write(&self) -> MaybeRwLockWriteGuard<'_, T> {
-            MaybeRwLockWriteGuard(unwrap_lock!(self.0.write()))
+            MaybeRwLockWriteGuard {
+                guard: unwrap_lock!(self.0.write()),
+                _held: Held::new(self),
+            }
+        }
+        pub fn try_write(&self) -> Option<MaybeRwLockWriteGuard<'_, T>> {
+            #[cfg(feature = "std")]
+            let guard = match self.0.try_write() {
+                Ok(guard) => Some(guard),
+                Err(std::sync::TryLockError::WouldBlock) => None,
+                Err(std::sync::TryLockError::Poisoned(error)) => panic!("{error}"),
+            };
+
+            #[cfg(not(feature = "std"))]
+            let guard = self.0.try_write();
+
+            guard.map(|guard| MaybeRwLockWriteGuard {
+                guard,
+                _held: Held::new(self),
+            })
         }
+
+        /// Returns `true` if the current thread holds a guard of this lock, in which case waiting
+        /// for the lock would never end. Always `false` without `std`, which cannot tell threads
+        /// apart.
+        pub fn is_held_here(&self) -> bool {
+            Held::is_held(self)
+        }
+    }
+
+    // the locks the current thread holds a guard of, by address
+    #[cfg(feature = "std")]
+    std::thread_local! {
+        static HELD: core::cell::RefCell<alloc::vec::Vec<usize>> =
+            const { core::cell::RefCell::new(alloc::vec::Vec::new()) };
     }
 
+    /// Records that the current thread holds a guard of a lock for as long as it lives.
+    struct Held(#[cfg(feature = "std")] usize);
+
+    impl Held {
+        fn new<T>(lock: &MaybeRwLock<T>) -> Self {
+            #[cfg(feature = "std")]
+            {
+                let addr = lock as *const MaybeRwLock<T> as usize;
+                HELD.with_borrow_mut(|held| held.push(addr));
+                Self(addr)
+            }
+
+            #[cfg(not(feature = "std"))]
+            {
+                let _ = lock;
+                Self()
+            }
+        }
+
+        fn is_held<T>(lock: &MaybeRwLock<T>) -> bool {
+            #[cfg(feature = "std")]
+            {
+                let addr = lock as *const MaybeRwLock<T> as usize;
+                HELD.with_borrow(|held| held.contains(&addr))
+            }
+
+            #[cfg(not(feature = "std"))]
+            {
+                let _ = lock;
+                false
+            }
+        }
+    }
+
+    #[cfg(feature = "std")]
+    impl Drop for Held {
+        fn drop(&mut self) {
+            // a thread that is exiting has already dropped its record
+            HELD.try_with(|held| {
+                let mut held = held.borrow_mut();
+                if let Some(index) = held.iter().rposition(|addr| *addr == self.0) {
+                    held.swap_remove(index);
+                }
+            })
+            .ok();
+        }
+    }
+
+    #[derive(Default)]
     pub struct MaybeMutex<T>(MutexImpl<T>);
     pub struct MaybeMutexGuard<'a, T>(MutexGuardImpl<'a, T>);
 
     impl<T> MaybeMutex<T> {
-        pub fn new(value: T) -> Self {
+        pub const fn new(value: T) -> Self {
             Self(MutexImpl::new(value))
         }
         pub fn lock(&self) -> MaybeMutexGuard<'_, T> {
@@ -92,18 +186,18 @@ mod sync {
     impl<'a, T> Deref for MaybeRwLockReadGuard<'a, T> {
         type Target = T;
         fn deref(&self) -> &Self::Target {
-            &self.0
+            &self.guard
         }
     }
     impl<'a, T> Deref for MaybeRwLockWriteGuard<'a, T> {
         type Target = T;
         fn deref(&self) -> &Self::Target {
-            &self.0
+            &self.guard
         }
     }
     impl<'a, T> DerefMut for MaybeRwLockWriteGuard<'a, T> {
         fn deref_mut(&mut self) -> &mut Self::Target {
-            &mut self.0
+            &mut self.guard
         }
     }
     impl<'a, T> Deref for MaybeMutexGuard<'a, T> {
@@ -123,6 +217,7 @@ mod sync {
 mod sync {
     use core::cell::{Ref, RefCell, RefMut};
 
+    #[derive(Default)]
     pub struct MaybeRwLock<T>(RefCell<T>);
     pub type MaybeRwLockReadGuard<'a, T> = Ref<'a, T>;
     pub type MaybeRwLockWriteGuard<'a, T> = RefMut<'a, T>;
@@ -137,13 +232,23 @@ mod sync {
         pub fn write(&self) -> MaybeRwLockWriteGuard<'_, T> {
             self.0.borrow_mut()
         }
+        pub fn try_write(&self) -> Option<MaybeRwLockWriteGuard<'_, T>> {
+            self.0.try_borrow_mut().ok()
+        }
+
+        /// Returns `true` if a guard of this lock is alive, which can only be held by the current
+        /// thread.
+        pub fn is_held_here(&self) -> bool {
+            self.0.try_borrow_mut().is_err()
+        }
     }
 
+    #[derive(Default)]
     pub struct MaybeMutex<T>(RefCell<T>);
     pub type MaybeMutexGuard<'a, T> = RefMut<'a, T>;
 
     impl<T> MaybeMutex<T> {
-        pub fn new(value: T) -> Self {
+        pub const fn new(value: T) -> Self {
             Self(RefCell::new(value))
         }
         pub fn lock(&self) -> MaybeMutexGuard<'_, T> {
@@ -152,9 +257,7 @@ mod sync {
     }
 }
 
-pub use sync::{
-    MaybeMutex, MaybeMutexGuard, MaybeRwLock, MaybeRwLockReadGuard, MaybeRwLockWriteGuard,
-};
+pub use sync::{MaybeMutex, MaybeRwLock, MaybeRwLockReadGuard, MaybeRwLockWriteGuard};
 
 pub trait MaybeSendSync: MaybeSend + MaybeSync {}
 impl<T: MaybeSend + MaybeSync> MaybeSendSync for T {}
@@ -0,0 +1,57 @@
+use crate::maybe::{MaybeLocalBoxStream, MaybeSend, MaybeSendSync, boxed_stream};
+use crate::{Getter, Model, Signal, SignalStatus};
+use alloc::vec::Vec;
+use futures::StreamExt;
+use futures::stream::SelectAll;
+
+/// Identifies a region of the UI that has to be refreshed whenever one of its signals changes.
+///
+/// Usually an enum with one variant per region, passed to `AppHandle::should_refresh`.
+pub trait RegionId: Clone + MaybeSend + 'static {
+    type Model: Model;
+
+    /// Groups the signals of the model into regions.
+    fn regions(getter: &Getter<Self::Model>) -> Regions<Self>;
+}
+
+/// The signals of every region, built by [`RegionId::regions`].
+pub struct Regions<R> {
+    signals: Vec<(R, MaybeLocalBoxStream<'static, SignalStatus>)>,
+}
+
+impl<R: Clone + MaybeSend + 'static> Regions<R> {
+    pub fn new() -> Self {
+        Self {
+            signals: Vec::new(),
+        }
+    }
+
+    /// Adds the given signal to the given region. A signal may belong to several regions.
+    pub fn signal<T: MaybeSendSync + 'static>(mut self, region: R, signal: &Signal<T>) -> Self {
+        self.signals
+            .push((region, boxed_stream(signal.subscribe())));
+        self
+    }
+
+    /// Returns a stream that yields the id of a region whenever one of its signals is flushed as
+    /// changed.
+    pub fn into_stream(self) -> MaybeLocalBoxStream<'static, R> {
+        let streams = self.signals.into_iter().map(|(region, subscriber)| {
+            let stream = subscriber.filter_map(move |status| {
+                let region = match status {
+                    SignalStatus::Changed => Some(region.clone()),
+                    SignalStatus::Destroyed => None,
+                };
+                async move { region }
+            });
+            boxed_stream(stream)
+        });
+        boxed_stream(streams.collect::<SelectAll<_>>())
+    }
+}
+
+impl<R: Clone + MaybeSend + 'static> Default for Regions<R> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
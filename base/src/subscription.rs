@@ -0,0 +1,146 @@
+//! Creates long-lived event sources tied to the state of a model.
+
+use crate::maybe::{MaybeLocalBoxStream, MaybeSend, MaybeSendSync, Shared, boxed_stream};
+use crate::{Application, CommandContext, CommandId};
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::vec::Vec;
+use core::any::TypeId;
+use core::fmt;
+use core::hash::Hash;
+use futures::{Stream, StreamExt};
+
+type RecipeRepr<T, ForApp> =
+    Box<dyn_Maybe!(Send FnOnce(CommandContext<ForApp>) -> MaybeLocalBoxStream<'static, T>)>;
+
+pub(crate) struct Recipe<T, ForApp: Application> {
+    pub(crate) id: CommandId,
+    pub(crate) stream_fn: RecipeRepr<T, ForApp>,
+}
+
+/// A set of event sources the host keeps alive for as long as the model asks for them.
+///
+/// The host re-evaluates [`crate::Model::subscriptions`] after every update. Subscriptions are
+/// identified by their key: a key that appears for the first time is started, a key that
+/// disappears is stopped and its stream dropped, and a key that is still present keeps running
+/// untouched.
+pub struct Subscription<T, ForApp: Application>(Vec<Recipe<T, ForApp>>);
+
+impl<T, ForApp: Application> Subscription<T, ForApp> {
+    /// Creates a [`Subscription`] that does nothing.
+    pub fn none() -> Self {
+        Self(Vec::new())
+    }
+
+    /// Creates a [`Subscription`] identified by the given key that runs the [`Stream`] returned
+    /// by the closure and produces each of its items.
+    pub fn run<K, F, S>(key: K, f: F) -> Self
+    where
+        K: Hash + Eq + MaybeSendSync + 'static,
+        F: FnOnce(CommandContext<ForApp>) -> S + MaybeSend + 'static,
+        S: Stream<Item = T> + MaybeSend + 'static,
+    {
+        Self(alloc::vec![Recipe {
+            id: CommandId::new(key),
+            stream_fn: Box::new(move |ctx| boxed_stream(f(ctx))),
+        }])
+    }
+
+    /// Combines the given subscriptions into a single [`Subscription`].
+    pub fn batch(subscriptions: impl IntoIterator<Item = Self>) -> Self {
+        Self(
+            subscriptions
+                .into_iter()
+                .flat_map(|subscription| subscription.0)
+                .collect(),
+        )
+    }
+
+    /// Maps the output of a [`Subscription`] with the given closure.
+    ///
+    /// The type of the closure is part of the identity of the resulting [`Subscription`], so the
+    /// same key can be reused by different child models.
+    pub fn map<O, F>(self, f: F) -> Subscription<O, ForApp>
+    where
+        T: 'static,
+        O: 'static,
+        F: Fn(T) -> O + MaybeSendSync + 'static,
+    {
+        let f = Shared::new(f);
+        Subscription(
+            self.0
+                .into_iter()
+                .map(|recipe| {
+                    let f = Shared::clone(&f);
+                    Recipe {
+                        id: CommandId::new((recipe.id, TypeId::of::<F>())),
+                        stream_fn: Box::new(move |ctx: CommandContext<ForApp>| {
+                            boxed_stream((recipe.stream_fn)(ctx).map(move |output| f(output)))
+                        }) as RecipeRepr<O, ForApp>,
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    /// Returns `true` if this [`Subscription`] has no event sources.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl<T, ForApp: Application> fmt::Debug for Subscription<T, ForApp> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple(&format!(
+            "Subscription<{}, {}>",
+            core::any::type_name::<T>(),
+            core::any::type_name::<ForApp>()
+        ))
+        .field(&self.0.iter().map(|r| &r.id).collect::<Vec<_>>())
+        .finish()
+    }
+}
+
+impl<T, ForApp: Application> Default for Subscription<T, ForApp> {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+impl<T, ForApp: Application> From<()> for Subscription<T, ForApp> {
+    fn from((): ()) -> Self {
+        Self::none()
+    }
+}
+
+pub(crate) fn into_recipes<T, ForApp: Application>(
+    subscription: Subscription<T, ForApp>,
+) -> Vec<Recipe<T, ForApp>> {
+    subscription.0
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::TestHost;
+    use crate::host::fixture::{LogMessage, TestApp};
+    use alloc::vec;
+
+    #[test]
+    fn only_starts_new_keys_and_stops_removed_ones() {
+        let mut host = TestHost::<TestApp>::defaults();
+        host.send(LogMessage::Subscribe(vec![1, 2]));
+        assert_eq!(host.entries(), ["start 1", "start 2"]);
+        host.send(LogMessage::Subscribe(vec![2, 3]));
+        assert_eq!(host.entries(), ["start 1", "start 2", "start 3"]);
+        // 1 was stopped, so it starts over
+        host.send(LogMessage::Subscribe(vec![1, 3]));
+        assert_eq!(host.entries()[3..], ["start 1"]);
+    }
+
+    #[test]
+    fn ignores_duplicate_keys() {
+        let mut host = TestHost::<TestApp>::defaults();
+        host.send(LogMessage::Subscribe(vec![1, 1]));
+        assert_eq!(host.entries(), ["start 1"]);
+    }
+}
@@ -3,4 +3,4 @@ extern crate emyu_base;
 pub use emyu_base::*;
 
 #[cfg(feature = "macros")]
-pub use emyu_macros::model;
+pub use emyu_macros::{Snapshot, model};
@@ -69,6 +69,9 @@ pub struct ModelArgs {
     pub updater: ModelProperties,
     pub getter: ModelProperties,
     pub for_app: Ident,
+    pub ignore_closed: bool,
+    pub sync: bool,
+    pub sync_fn_meta: Vec<ProcessedMeta>,
 }
 
 impl ModelArgs {
@@ -96,7 +99,9 @@ impl ModelArgs {
             .ok_or_else(|| syn::Error::new(span, "`dispatcher` is required"))?
             .into_config();
         let message = MessageEnumProperties::from_config(
-            raw.message,
+            raw.message
+                .map(raw::MessageDef::into_config)
+                .unwrap_or_default(),
             model_name,
             crate_,
             flutter_rust_bridge,
@@ -135,6 +140,14 @@ impl ModelArgs {
                 flutter_rust_bridge,
             ),
             for_app,
+            ignore_closed: config.ignore_closed,
+            sync: config.sync,
+            sync_fn_meta: include_if_frb(
+                iter::empty(),
+                || utils::frb_sync(crate_),
+                flutter_rust_bridge && config.sync,
+            )
+            .collect(),
         }
     }
 }
@@ -211,6 +224,10 @@ impl NewMethodArgs {
             return Err(invalid_position_error(span, "#[emyu(name(...))]"));
         };
 
+        if raw.reply {
+            return Err(invalid_position_error(span, "#[emyu(reply)]"));
+        }
+
         Ok(())
     }
 
@@ -247,6 +264,7 @@ pub struct UpdaterGetterMethodArgs {
     pub message: MessageProperties,
     pub fn_name: Ident,
     pub fn_meta: Vec<ProcessedMeta>,
+    pub reply: bool,
 }
 
 impl UpdaterGetterMethodArgs {
@@ -272,6 +290,7 @@ impl UpdaterGetterMethodArgs {
                     W::fn_meta_owned(&raw.meta).collect()
                 }
             },
+            reply: raw.reply,
         }
     }
 
@@ -314,6 +333,10 @@ impl UpdaterGetterMethodArgs {
             return Err(invalid_position_error(span, "#[emyu(meta(updater(...)))]"));
         }
 
+        if raw.reply {
+            return Err(invalid_position_error(span, "#[emyu(reply)]"));
+        }
+
         Ok(())
     }
 
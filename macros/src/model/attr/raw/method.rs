@@ -13,4 +13,7 @@ pub struct MethodArgs {
 
     #[darling(default)]
     pub meta: Option<MetaConfig>,
+
+    #[darling(default)]
+    pub reply: bool,
 }
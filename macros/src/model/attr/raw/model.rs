@@ -4,7 +4,7 @@ use darling::ast::NestedMeta;
 use proc_macro2::{Ident, Span};
 use syn::Meta;
 
-/// ```rust
+/// ```rust,ignore
 /// #[emyu::model(
 ///     // Specify the application this model is for. Required.
 ///     for_app = "MyCoolApp",
@@ -67,6 +67,18 @@ use syn::Meta;
 ///                 getter(qux),     // inner attributes for the generated `getter` struct
 ///             ),
 ///         ),
+///
+///         // Makes the generated updater functions return nothing, silently dropping the messages
+///         // sent after the host has stopped. By default, they return
+///         // `Result<(), emyu::Error>`.
+///         ignore_closed,
+///
+///         // Makes the generated updater functions synchronous: they send their message right away
+///         // and fail with `emyu::Error::HostChannelFull` if the host cannot take it yet. Along with
+///         // `ignore_closed`, they block until the host can take it instead, see
+///         // `emyu::Updater::send_now`, or drop the message without the `std` feature. Updater
+///         // functions marked with `#[emyu(reply)]` stay `async`.
+///         sync,
 ///     ),
 ///
 ///     // (only when `frb-compat` feature is enabled) Adds special attributes and behavior for
@@ -118,6 +130,11 @@ use syn::Meta;
 ///             message(derive(Clone)), // outer attributes for the message struct
 ///             updater(baz),           // attributes for the updater function
 ///         ),
+///
+///         // Makes the updater function wait until the update has been applied and its signals
+///         // flushed, or until the command it returned has finished. The message variant gets an
+///         // extra `__reply: emyu::Reply` field.
+///         reply,
 ///     )]
 ///     pub(crate) fn set_name(
 ///         &mut self,
@@ -167,7 +184,7 @@ pub struct ModelArgs {
     pub for_app: Ident,
 
     #[darling(default)]
-    pub message: MessageConfig,
+    pub message: Option<MessageDef>,
 
     #[darling(default)]
     pub dispatcher: Option<DispatcherDef>,
@@ -261,4 +278,10 @@ pub struct DispatcherConfig {
 
     #[darling(default)]
     pub meta: Option<MetaConfig>,
+
+    #[darling(default)]
+    pub ignore_closed: bool,
+
+    #[darling(default)]
+    pub sync: bool,
 }
@@ -27,10 +27,7 @@ impl<'a> ModelContext<'a> {
         let model_ty = self.model_ty;
         let for_app = &self.args.for_app;
         let message_name = &self.args.message.name;
-        let model_fns = self
-            .updaters
-            .iter()
-            .map(ParsedUpdaterFn::generate_model_fn);
+        let model_fns = self.updaters.iter().map(ParsedUpdaterFn::generate_model_fn);
         let match_cases = self
             .updaters
             .iter()
@@ -71,7 +68,10 @@ impl<'a> ModelContext<'a> {
         let vis = &self.struct_vis;
         let name = &self.args.message.name;
         let outer_meta = &self.args.message.outer_meta;
-        let variants = self.updaters.iter().map(|u| u.generate_message_variant());
+        let variants = self
+            .updaters
+            .iter()
+            .map(|u| u.generate_message_variant(&self.crate_));
 
         quote! {
             #(#[#outer_meta])*
@@ -174,7 +174,7 @@ impl<'a> ModelContext<'a> {
             |a| &a.updater,
             |new_fn, crate_, dispatcher_name| new_fn.generate_for_updater(crate_, dispatcher_name),
             |m| &m.updaters,
-            |u| u.generate_updater_fn(&self.args.message.name),
+            |u| u.generate_updater_fn(&self.args, crate_),
         )
     }
 }
@@ -289,14 +289,24 @@ impl<'a> ParsedUpdaterGetterFn<'a> {
 }
 
 impl<'a> ParsedUpdaterFn<'a> {
-    fn generate_message_variant(&self) -> TokenStream {
+    fn reply_field(&self) -> Option<Ident> {
+        self.common
+            .method_args
+            .reply
+            .then(|| Ident::new("__reply", Span::call_site()))
+    }
+
+    fn generate_message_variant(&self, crate_: &ThisCrate) -> TokenStream {
         let variant_name = &self.common.method_args.message.name;
         let outer_meta = &self.common.method_args.message.outer_meta;
         let fields = self.fn_args.iter().map(|fa| fa.generate_field());
+        let reply_field = self
+            .reply_field()
+            .map(|reply| quote! { #reply: #crate_::Reply });
 
         quote! {
             #(#[#outer_meta])*
-            #variant_name { #(#fields),* },
+            #variant_name { #(#fields,)* #reply_field },
         }
     }
 
@@ -314,8 +324,15 @@ impl<'a> ParsedUpdaterFn<'a> {
             }}
         };
 
-        quote! {
-            #message_name::#variant_name { #(#field_names),* } => #rhs,
+        match self.reply_field() {
+            Some(reply) => quote! {
+                #message_name::#variant_name { #(#field_names,)* #reply } => {
+                    #crate_::Command::with_reply(#rhs, #reply)
+                }
+            },
+            None => quote! {
+                #message_name::#variant_name { #(#field_names),* } => #rhs,
+            },
         }
     }
 
@@ -328,7 +345,10 @@ impl<'a> ParsedUpdaterFn<'a> {
         }
     }
 
-    fn generate_updater_fn(&self, message_name: &Ident) -> TokenStream {
+    fn generate_updater_fn(&self, args: &ModelArgs, crate_: &ThisCrate) -> TokenStream {
+        let message_name = &args.message.name;
+        let ignore_closed = args.ignore_closed;
+        let sync = args.sync && !self.common.method_args.reply;
         self.common
             .generate_updater_getter_fn(|vis, meta, fn_name, variant_name| {
                 let field_names = self.fn_args.iter().map(|fa| fa.name).collect::<Vec<_>>();
@@ -338,10 +358,68 @@ impl<'a> ParsedUpdaterFn<'a> {
                     .map(|fa| fa.generate_fn_arg())
                     .collect::<Vec<_>>();
 
-                quote! {
-                    #(#[#meta])*
-                    #vis async fn #fn_name(&mut self, #(#fn_args),*) {
-                        self.0.send(#message_name::#variant_name { #(#field_names),* }).await
+                let message = |reply: Option<&Ident>| {
+                    quote! { #message_name::#variant_name { #(#field_names,)* #reply } }
+                };
+                let body = match (self.reply_field(), ignore_closed) {
+                    (Some(reply), true) => {
+                        let message = message(Some(&reply));
+                        quote! { let _ = self.0.request(|#reply| #message).await; }
+                    }
+                    (Some(reply), false) => {
+                        let message = message(Some(&reply));
+                        quote! { self.0.request(|#reply| #message).await }
+                    }
+                    (None, true) if sync => {
+                        let message = message(None);
+                        quote! { let _ = self.0.send_now(#message); }
+                    }
+                    (None, true) => {
+                        let message = message(None);
+                        quote! { self.0.send(#message).await }
+                    }
+                    (None, false) if sync => {
+                        let message = message(None);
+                        quote! {
+                            self.0
+                                .try_send_now(#message)
+                                .map_err(::core::convert::From::from)
+                        }
+                    }
+                    (None, false) => {
+                        let message = message(None);
+                        quote! {
+                            self.0
+                                .try_send(#message)
+                                .await
+                                .map_err(::core::convert::From::from)
+                        }
+                    }
+                };
+                let (asyncness, sync_meta) = if sync {
+                    (None, args.sync_fn_meta.as_slice())
+                } else {
+                    (Some(quote! { async }), [].as_slice())
+                };
+
+                if ignore_closed {
+                    quote! {
+                        #(#[#meta])*
+                        #(#[#sync_meta])*
+                        #vis #asyncness fn #fn_name(&mut self, #(#fn_args),*) {
+                            #body
+                        }
+                    }
+                } else {
+                    quote! {
+                        #(#[#meta])*
+                        #(#[#sync_meta])*
+                        #vis #asyncness fn #fn_name(
+                            &mut self,
+                            #(#fn_args),*
+                        ) -> ::core::result::Result<(), #crate_::Error> {
+                            #body
+                        }
                     }
                 }
             })
@@ -381,13 +459,13 @@ impl<'a> ParsedGetterFn<'a> {
         quote! {
             #struct_decl
             impl #crate_::ModelGetterMessage for #message_name {
-                type Data = #ret_ty;
+                type Data = <#ret_ty as #crate_::__macros::GetterField>::Data;
             }
             impl #crate_::ModelGetterHandler<#message_name> for #model_ty {
                 fn getter(
                     &self,
-                ) -> #crate_::Signal<#ret_ty> {
-                    ::core::clone::Clone::clone(&self.#field_name)
+                ) -> #crate_::Signal<<#ret_ty as #crate_::__macros::GetterField>::Data> {
+                    #crate_::__macros::GetterField::__signal(&self.#field_name, #crate_::__token())
                 }
             }
         }
@@ -395,12 +473,12 @@ impl<'a> ParsedGetterFn<'a> {
 
     fn generate_getter_fn(&self, crate_: &ThisCrate) -> TokenStream {
         self.common
-            .generate_updater_getter_fn(|vis, meta, fn_name, message_name| {
+            .generate_updater_getter_fn(|vis, meta, fn_name, _| {
                 let ret_ty = self.ret_ty;
                 quote! {
                     #(#[#meta])*
-                    #vis fn #fn_name(&mut self) -> #crate_::Signal<#ret_ty> {
-                        self.0.get::<#message_name>()
+                    #vis fn #fn_name(&mut self) -> #ret_ty {
+                        self.0.__field(|model| &model.#fn_name, #crate_::__token())
                     }
                 }
             })
@@ -69,7 +69,7 @@ impl<'a> ParsedFnFirstPass<'a> {
         flutter_rust_bridge: bool,
     ) -> syn::Result<Self> {
         let args = raw::MethodArgs::from_attributes(&item.attrs)?;
-        let mut kind = FnKind::analyze(item, args, crate_, flutter_rust_bridge)?;
+        let kind = FnKind::analyze(item, args, crate_, flutter_rust_bridge)?;
         Ok(Self {
             vis: &item.vis,
             fn_args: item
@@ -161,6 +161,22 @@ impl<'a> FnKind<'a> {
             }
         }
 
+        // a getter returns the type of its field: a signal, or a collection wrapping one
+        fn extract_getter_ty(ret_ty: &ReturnType) -> Option<&Type> {
+            if let ReturnType::Type(_, ty) = ret_ty
+                && let Type::Path(TypePath {
+                    path: Path { segments, .. },
+                    ..
+                }) = &**ty
+                && let Some(PathSegment { ident, .. }) = segments.last()
+                && (ident == "Signal" || ident == "SignalVec" || ident == "SignalMap")
+            {
+                Some(ty)
+            } else {
+                None
+            }
+        }
+
         fn extract_inner_signal_ty(ret_ty: &ReturnType) -> Option<&Type> {
             if let ReturnType::Type(_, ty) = ret_ty
                 && let Type::Path(TypePath {
@@ -187,20 +203,28 @@ impl<'a> FnKind<'a> {
         let has_no_fn_args = item.sig.inputs.is_empty()
             || (item.sig.inputs.len() == 1
                 && matches!(item.sig.inputs.first(), Some(FnArg::Receiver(_))));
-        let (self_ty, ret_ty, block) = (
+        let (self_ty, ret_ty, getter_ty, block) = (
             SelfTy::analyze(item.sig.inputs.iter()),
             extract_inner_signal_ty(&item.sig.output),
+            extract_getter_ty(&item.sig.output),
             item.block.as_ref(),
         );
 
-        match (fn_name.as_str(), self_ty, ret_ty, has_no_fn_args, block) {
-            ("new", None, None, true, None) => Ok(Self::New(NewMethodArgs::parse(
+        match (
+            fn_name.as_str(),
+            self_ty,
+            ret_ty,
+            getter_ty,
+            has_no_fn_args,
+            block,
+        ) {
+            ("new", None, None, _, true, None) => Ok(Self::New(NewMethodArgs::parse(
                 args,
                 item.sig.span(),
                 crate_,
                 flutter_rust_bridge,
             )?)),
-            (_, Some(SelfTy::Mutable), command_ty, _, Some(block)) => Ok(Self::Updater {
+            (_, Some(SelfTy::Mutable), command_ty, _, _, Some(block)) => Ok(Self::Updater {
                 args: UpdaterGetterMethodArgs::parse_updater(
                     args,
                     &item.sig.ident,
@@ -211,7 +235,7 @@ impl<'a> FnKind<'a> {
                 command_ty,
                 block,
             }),
-            (_, Some(SelfTy::Shared), Some(ty), true, None) => Ok(Self::Getter {
+            (_, Some(SelfTy::Shared), _, Some(ty), true, None) => Ok(Self::Getter {
                 args: UpdaterGetterMethodArgs::parse_getter(
                     args,
                     &item.sig.ident,
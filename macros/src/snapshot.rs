@@ -0,0 +1,95 @@
+//! `#[derive(emyu::Snapshot)]` macro implementation.
+use crate::utils::ThisCrate;
+use darling::{FromDeriveInput, FromField, ast};
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{DeriveInput, Generics, Ident, Index, Member, Type, parse_quote};
+
+#[derive(FromDeriveInput)]
+#[darling(supports(struct_any))]
+struct SnapshotInput {
+    ident: Ident,
+    generics: Generics,
+    data: ast::Data<(), SnapshotField>,
+}
+
+#[derive(FromField)]
+#[darling(attributes(snapshot))]
+struct SnapshotField {
+    ident: Option<Ident>,
+    ty: Type,
+
+    // `#[snapshot(clone)]`: snapshot the field by cloning it, for plain data that holds no signal
+    #[darling(default)]
+    clone: bool,
+}
+
+pub fn build(input: DeriveInput) -> syn::Result<TokenStream> {
+    let input = SnapshotInput::from_derive_input(&input)?;
+    let crate_ = ThisCrate::default();
+    let fields = input
+        .data
+        .take_struct()
+        .expect("only structs are supported")
+        .fields;
+
+    let mut generics = input.generics;
+    let where_clause = generics.make_where_clause();
+    for field in &fields {
+        let ty = &field.ty;
+        where_clause.predicates.push(if field.clone {
+            parse_quote!(#ty: ::core::clone::Clone)
+        } else {
+            parse_quote!(#ty: #crate_::Snapshot)
+        });
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let members = fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| match &field.ident {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(Index {
+                index: index as u32,
+                span: Span::call_site(),
+            }),
+        });
+    let (snapshots, restores): (Vec<_>, Vec<_>) = fields
+        .iter()
+        .zip(members)
+        .map(|(field, member)| {
+            if field.clone {
+                (
+                    quote! { #member: ::core::clone::Clone::clone(&self.#member), },
+                    quote! { self.#member = ::core::clone::Clone::clone(&snapshot.#member); },
+                )
+            } else {
+                (
+                    quote! { #member: #crate_::Snapshot::snapshot(&self.#member), },
+                    quote! { #crate_::Snapshot::restore(&mut self.#member, &snapshot.#member); },
+                )
+            }
+        })
+        .unzip();
+
+    let ident = &input.ident;
+    let snapshot = if fields.is_empty() {
+        quote!(_snapshot)
+    } else {
+        quote!(snapshot)
+    };
+    Ok(quote! {
+        impl #impl_generics #crate_::Snapshot for #ident #ty_generics #where_clause {
+            fn snapshot(&self) -> Self {
+                Self {
+                    #(#snapshots)*
+                }
+            }
+
+            fn restore(&mut self, #snapshot: &Self) {
+                #(#restores)*
+            }
+        }
+    })
+}
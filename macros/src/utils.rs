@@ -77,6 +77,7 @@ impl Parse for InterfaceImpl {
 
 /// Parses attributes into type T, returning the parsed value and the remaining attributes
 /// (excluding the ones consumed by T, marked by "emyu").
+#[allow(dead_code)]
 pub fn extract_emyu_attrs<T: FromAttributes>(
     attributes: &[Attribute],
 ) -> syn::Result<(Vec<&Attribute>, T)> {